@@ -0,0 +1,31 @@
+//! Embeds the commit and time of the build, which `/about`
+//! responds.
+
+use std::env;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    // `GIT_SHA` is set where there is no repository, like in
+    // the Docker build
+    let sha = env::var("GIT_SHA").ok()
+        .or_else(|| {
+            let output = Command::new("git").args(["rev-parse", "HEAD"]).output().ok()?;
+            output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    // Reproducible builds set the time themselves
+    let built_at = env::var("SOURCE_DATE_EPOCH").ok()
+        .and_then(|x| x.parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs()));
+
+    println!("cargo:rustc-env=GIT_SHA={}", sha);
+    println!("cargo:rustc-env=BUILT_AT={}", built_at);
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
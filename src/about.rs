@@ -0,0 +1,64 @@
+//! The version and build of the checker and what it is busy
+//! with, for operators.
+
+use std::time::{Instant, SystemTime};
+use serde::Serialize;
+use crate::unix_time;
+
+/// When the process started.
+pub struct Started {
+    at: SystemTime,
+    instant: Instant,
+}
+
+impl Started {
+    pub fn now() -> Started {
+        Started { at: SystemTime::now(), instant: Instant::now() }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct About {
+    /// The version of the crate.
+    pub version: &'static str,
+
+    /// The commit it was built from, `unknown` outside of a
+    /// repository.
+    pub git_sha: &'static str,
+
+    /// When it was built, in seconds since the Unix epoch.
+    pub built_at: u64,
+
+    /// When the process started, in seconds since the Unix
+    /// epoch.
+    pub started_at: u64,
+
+    /// How long the process has been running, in seconds.
+    pub uptime: u64,
+
+    /// The number of monitored servers, configured and added
+    /// at runtime.
+    pub monitored_servers: usize,
+
+    /// The number of cached statuses.
+    pub cache_entries: usize,
+
+    /// The number of Java Edition pings waiting for their
+    /// server.
+    pub pings_in_flight: i64,
+}
+
+impl About {
+    pub fn new(started: &Started, monitored_servers: usize, cache_entries: usize, pings_in_flight: i64) -> About {
+        About {
+            version: env!("CARGO_PKG_VERSION"),
+            git_sha: env!("GIT_SHA"),
+            built_at: env!("BUILT_AT").parse().unwrap_or(0),
+            started_at: unix_time(started.at),
+            uptime: started.instant.elapsed().as_secs(),
+            monitored_servers,
+            cache_entries,
+            pings_in_flight,
+        }
+    }
+}
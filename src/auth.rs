@@ -0,0 +1,104 @@
+//! Guards for the routes only operators or API key holders
+//! may use.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use crate::config::Config;
+use crate::ratelimit::{RateLimiter, RetryAfter};
+
+/// Whether `a` and `b` are equal, comparing every byte so
+/// how long it takes doesn't tell how much of a guessed token
+/// is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// A request authenticated with the configured admin token
+/// as `Authorization: Bearer <token>`. Admin routes refuse
+/// every request if there is no token configured.
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = request.rocket().state::<Config>().and_then(|x| x.admin_token.as_deref());
+        let given = request.headers().get_one("Authorization").and_then(|x| x.strip_prefix("Bearer "));
+
+        match (token, given) {
+            (Some(token), Some(given)) if constant_time_eq(token.as_bytes(), given.as_bytes()) => Outcome::Success(Admin),
+            _ => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// The configured API keys with the rate limit of each.
+pub struct ApiKeys {
+    keys: HashMap<String, RateLimiter<()>>,
+    required: bool,
+}
+
+impl ApiKeys {
+    pub fn new(config: &Config) -> Result<ApiKeys, String> {
+        let mut keys = HashMap::new();
+        for (name, key) in &config.api_keys {
+            if key.key.is_empty() {
+                return Err(format!("API key `{}` has no key", name));
+            }
+            let limiter = RateLimiter::new(key.per_minute, key.burst);
+            if keys.insert(key.key.clone(), limiter).is_some() {
+                return Err(format!("API key `{}` is not unique", name));
+            }
+        }
+        if config.require_api_key && keys.is_empty() {
+            return Err("API keys are required but none are configured".to_string());
+        }
+        Ok(ApiKeys { keys, required: config.require_api_key })
+    }
+
+    /// Finds the key the request was sent with as `X-API-Key`
+    /// header, compared with every key in constant time, and
+    /// returns its rate limit. Keys aren't taken from the query,
+    /// which ends up in the logs.
+    pub fn find(&self, request: &Request<'_>) -> Option<&RateLimiter<()>> {
+        let given = request.headers().get_one("X-API-Key")?;
+        self.keys.iter().fold(None, |found, (key, limiter)| match constant_time_eq(key.as_bytes(), given.as_bytes()) {
+            true => Some(limiter),
+            false => found,
+        })
+    }
+}
+
+/// A request with a valid API key within its rate limit, or
+/// without a key if they aren't required.
+pub struct ApiKey;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiKey {
+    /// The time until the key may be used again if it is over
+    /// its limit, none if the key is missing or invalid.
+    type Error = Option<Duration>;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let keys = match request.rocket().state::<ApiKeys>() {
+            Some(keys) => keys,
+            None => return Outcome::Success(ApiKey),
+        };
+
+        let given = request.headers().contains("X-API-Key");
+        match keys.find(request) {
+            Some(limiter) => match limiter.take(()) {
+                Ok(()) => Outcome::Success(ApiKey),
+                Err(retry_after) => {
+                    request.local_cache(|| RetryAfter(Some(retry_after)));
+                    Outcome::Error((Status::TooManyRequests, Some(retry_after)))
+                }
+            },
+            None if given || keys.required => Outcome::Error((Status::Unauthorized, None)),
+            None => Outcome::Success(ApiKey),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Banners of a server in the style of its entry in the
+//! multiplayer server list, rendered to PNG.
+
+use std::io::Cursor;
+use ab_glyph::{point, Font, FontArc, PxScale, ScaleFont};
+use image::{imageops, ImageFormat, Rgba, RgbaImage};
+use mcstatus_checker::chat::Segment;
+use mcstatus_checker::java::Ping;
+use crate::favicon;
+
+const WIDTH: u32 = 700;
+const HEIGHT: u32 = 80;
+const PADDING: u32 = 8;
+const ICON_SIZE: u32 = 64;
+
+const BACKGROUND: u32 = 0x202020;
+const BORDER: u32 = 0x555555;
+const WHITE: u32 = 0xFFFFFF;
+const GRAY: u32 = 0xAAAAAA;
+const DARK_GRAY: u32 = 0x555555;
+const RED: u32 = 0xFF5555;
+const GREEN: u32 = 0x55FF55;
+
+const NAME_SIZE: f32 = 18.0;
+const TEXT_SIZE: f32 = 16.0;
+
+/// Renders banners with a TrueType font.
+pub struct BannerRenderer {
+    font: FontArc,
+}
+
+impl BannerRenderer {
+    /// Loads the font to render with from `path`.
+    pub fn load(path: &str) -> Result<BannerRenderer, String> {
+        let data = std::fs::read(path).map_err(|e| format!("can't read the banner font `{}`: {}", path, e))?;
+        let font = FontArc::try_from_vec(data).map_err(|_| format!("invalid banner font `{}`", path))?;
+        Ok(BannerRenderer { font })
+    }
+
+    /// Renders the banner of the server `name` as a PNG: its
+    /// favicon, name and MOTD on the left, the player count
+    /// and latency bars on the right, or "Offline" without a
+    /// `ping`.
+    pub fn render(&self, name: &str, ping: Option<&Ping>) -> Vec<u8> {
+        let mut image = RgbaImage::from_pixel(WIDTH, HEIGHT, rgba(BACKGROUND));
+        for x in 0..WIDTH {
+            image.put_pixel(x, 0, rgba(BORDER));
+            image.put_pixel(x, HEIGHT - 1, rgba(BORDER));
+        }
+        for y in 0..HEIGHT {
+            image.put_pixel(0, y, rgba(BORDER));
+            image.put_pixel(WIDTH - 1, y, rgba(BORDER));
+        }
+
+        let icon = ping
+            .and_then(|x| x.status.favicon.as_deref())
+            .and_then(favicon::decode)
+            .and_then(|x| image::load_from_memory_with_format(&x, ImageFormat::Png).ok());
+        match icon {
+            Some(icon) => {
+                let icon = imageops::resize(&icon.to_rgba8(), ICON_SIZE, ICON_SIZE, imageops::FilterType::Nearest);
+                imageops::overlay(&mut image, &icon, PADDING as i64, PADDING as i64);
+            }
+            None => {
+                fill(&mut image, PADDING, PADDING, ICON_SIZE, ICON_SIZE, DARK_GRAY);
+            }
+        }
+
+        let bars_width = 5 * 4;
+        let bars_x = WIDTH - PADDING - bars_width;
+        let status = match ping {
+            Some(ping) => format!("{}/{}", ping.status.players.online, ping.status.players.max),
+            None => "Offline".to_string(),
+        };
+        let status_width = self.text_width(&status, TEXT_SIZE);
+        let status_x = bars_x as f32 - 8.0 - status_width;
+        let status_style = TextStyle { size: TEXT_SIZE, color: if ping.is_some() { GRAY } else { RED }, bold: false };
+        self.draw_text(&mut image, &status, (status_x, 24.0), status_style, WIDTH as f32);
+        self.draw_bars(&mut image, bars_x, ping);
+
+        let text_x = (PADDING * 2 + ICON_SIZE) as f32;
+        let name_style = TextStyle { size: NAME_SIZE, color: WHITE, bold: false };
+        self.draw_text(&mut image, name, (text_x, 24.0), name_style, status_x - 8.0);
+        if let Some(ping) = ping {
+            let mut lines = split_lines(ping.status.description.segments()).into_iter();
+            for baseline in [48.0, 68.0] {
+                let mut x = text_x;
+                for segment in lines.next().unwrap_or_default() {
+                    let style = TextStyle {
+                        size: TEXT_SIZE,
+                        color: segment.style.color.map_or(GRAY, |x| x.rgb()),
+                        bold: segment.style.bold,
+                    };
+                    x = self.draw_text(&mut image, &segment.text, (x, baseline), style, (WIDTH - PADDING) as f32);
+                }
+            }
+        }
+
+        let mut png = Vec::new();
+        image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap_or_default();
+        png
+    }
+
+    /// Draws 5 bars like the connection icon of the server
+    /// list, more of them lit the lower the latency.
+    fn draw_bars(&self, image: &mut RgbaImage, x: u32, ping: Option<&Ping>) {
+        let lit = match ping.map(|x| x.latency) {
+            Some(Some(latency)) => match latency.as_millis() {
+                0..=149 => 5,
+                150..=299 => 4,
+                300..=599 => 3,
+                600..=999 => 2,
+                _ => 1,
+            },
+            Some(None) => 5,
+            None => 0,
+        };
+        for i in 0..5 {
+            let height = 4 + i * 3;
+            let color = if i < lit { GREEN } else { DARK_GRAY };
+            fill(image, x + i * 4, 26 - height, 3, height, color);
+        }
+    }
+
+    fn text_width(&self, text: &str, size: f32) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(size));
+        text.chars().map(|c| font.h_advance(font.glyph_id(c))).sum()
+    }
+
+    /// Draws `text` with a shadow like Minecraft does, from
+    /// `x` on the `baseline` up to `limit`. Returns where the
+    /// text ended.
+    fn draw_text(&self, image: &mut RgbaImage, text: &str, (mut x, baseline): (f32, f32), style: TextStyle, limit: f32) -> f32 {
+        let scale = PxScale::from(style.size);
+        let font = self.font.as_scaled(scale);
+        let shadow = (style.color >> 2) & 0x3F3F3F;
+        // Bold text is drawn twice, one pixel apart
+        let layers: &[(f32, f32, u32)] = if style.bold {
+            &[(1.5, 1.5, shadow), (2.5, 1.5, shadow), (0.0, 0.0, style.color), (1.0, 0.0, style.color)]
+        } else {
+            &[(1.5, 1.5, shadow), (0.0, 0.0, style.color)]
+        };
+
+        for c in text.chars() {
+            let id = font.glyph_id(c);
+            let advance = font.h_advance(id) + if style.bold { 1.0 } else { 0.0 };
+            if x + advance > limit {
+                break;
+            }
+            for &(dx, dy, color) in layers {
+                let glyph = id.with_scale_and_position(scale, point(x + dx, baseline + dy));
+                if let Some(outline) = self.font.outline_glyph(glyph) {
+                    let bounds = outline.px_bounds();
+                    outline.draw(|gx, gy, coverage| {
+                        let px = bounds.min.x as i32 + gx as i32;
+                        let py = bounds.min.y as i32 + gy as i32;
+                        if px >= 0 && py >= 0 && (px as u32) < WIDTH && (py as u32) < HEIGHT {
+                            blend(image.get_pixel_mut(px as u32, py as u32), color, coverage);
+                        }
+                    });
+                }
+            }
+            x += advance;
+        }
+        x
+    }
+}
+
+/// How a piece of text is drawn.
+#[derive(Debug, Clone, Copy)]
+struct TextStyle {
+    size: f32,
+    color: u32,
+    bold: bool,
+}
+
+/// Splits the segments of a MOTD at its line breaks.
+fn split_lines(segments: Vec<Segment>) -> Vec<Vec<Segment>> {
+    let mut lines = vec![Vec::new()];
+    for segment in segments {
+        for (i, part) in segment.text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Vec::new());
+            }
+            if !part.is_empty() {
+                lines.last_mut().unwrap().push(Segment { text: part.to_string(), style: segment.style });
+            }
+        }
+    }
+    lines
+}
+
+fn rgba(rgb: u32) -> Rgba<u8> {
+    Rgba([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 0xFF])
+}
+
+fn fill(image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: u32) {
+    for py in y..(y + height).min(HEIGHT) {
+        for px in x..(x + width).min(WIDTH) {
+            image.put_pixel(px, py, rgba(color));
+        }
+    }
+}
+
+fn blend(pixel: &mut Rgba<u8>, color: u32, coverage: f32) {
+    let color = rgba(color);
+    for i in 0..3 {
+        pixel.0[i] = (pixel.0[i] as f32 * (1.0 - coverage) + color.0[i] as f32 * coverage).round() as u8;
+    }
+}
@@ -0,0 +1,195 @@
+//! Bedrock Edition status over the RakNet
+//! [unconnected ping](https://wiki.vg/Raknet_Protocol#Unconnected_Ping).
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use tokio::net::{lookup_host, UdpSocket};
+use crate::PingError;
+
+const UNCONNECTED_PING: u8 = 0x01;
+const UNCONNECTED_PONG: u8 = 0x1c;
+const MAGIC: [u8; 16] = [
+    0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe, 0xfe,
+    0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78,
+];
+
+/// Contains the online and maximum player counts.
+#[derive(Debug, Serialize)]
+pub struct BedrockPlayers {
+    /// The configured maximum number of players for the
+    /// server.
+    pub max: u32,
+
+    /// The number of players currently online.
+    pub online: u32,
+}
+
+/// The decoded server ID string of an unconnected pong.
+#[derive(Debug, Serialize)]
+pub struct BedrockStatus {
+    /// The edition of the server, `MCPE` or `MCEE` for
+    /// Education Edition.
+    pub edition: String,
+
+    /// The lines of the server's MOTD.
+    pub motd: Vec<String>,
+
+    /// The server's network protocol version.
+    pub protocol: u32,
+
+    /// The server's Minecraft version, i.e. "1.19.2".
+    pub version: String,
+
+    /// Information about currently online players.
+    pub players: BedrockPlayers,
+
+    /// The server's unique ID.
+    pub server_id: String,
+
+    /// The default gamemode, i.e. "Survival".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gamemode: Option<String>,
+
+    /// The numeric ID of the default gamemode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gamemode_id: Option<u32>,
+
+    /// The IPv4 port the server listens on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port_v4: Option<u16>,
+
+    /// The IPv6 port the server listens on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port_v6: Option<u16>,
+}
+
+/// Sends an unconnected ping to `host:port` and waits up to
+/// `timeout` for the pong, trying `retry` times since the
+/// datagrams may get lost.
+#[tracing::instrument(name = "bedrock_ping", skip(timeout))]
+pub async fn ping_bedrock(host: &str, port: u16, timeout: Duration, retry: usize) -> Result<BedrockStatus, PingError> {
+    let mut last_err = PingError::ProtocolError;
+    for _ in 0..retry {
+        match tokio::time::timeout(timeout, ping(host, port)).await {
+            Ok(Ok(x)) => {
+                return Ok(x);
+            }
+            Ok(Err(e)) => {
+                last_err = e;
+            }
+            Err(_) => {
+                last_err = PingError::Timeout;
+            }
+        }
+    }
+    Err(last_err)
+}
+
+async fn ping(host: &str, port: u16) -> Result<BedrockStatus, PingError> {
+    let addr = lookup_host((host, port)).await
+        .map_err(|_| PingError::ProtocolError)?
+        .next()
+        .ok_or(PingError::ProtocolError)?;
+    let bind = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(bind).await.map_err(|_| PingError::ProtocolError)?;
+    socket.connect(addr).await.map_err(|e| PingError::io(&e))?;
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    let mut packet = Vec::with_capacity(33);
+    packet.push(UNCONNECTED_PING);
+    packet.extend_from_slice(&time.to_be_bytes());
+    packet.extend_from_slice(&MAGIC);
+    packet.extend_from_slice(&(std::process::id() as u64).to_be_bytes());
+    socket.send(&packet).await.map_err(|e| PingError::io(&e))?;
+
+    let mut buffer = [0u8; 2048];
+    let len = socket.recv(&mut buffer).await.map_err(|e| PingError::io(&e))?;
+    parse_pong(&buffer[..len])
+}
+
+/// Parses an unconnected pong packet: the packet ID, the
+/// echoed time, the server GUID, the magic and finally the
+/// length-prefixed, `;` separated server ID string.
+fn parse_pong(packet: &[u8]) -> Result<BedrockStatus, PingError> {
+    if packet.len() < 35 || packet[0] != UNCONNECTED_PONG || packet[17..33] != MAGIC {
+        return Err(PingError::ProtocolError);
+    }
+    let len = u16::from_be_bytes([packet[33], packet[34]]) as usize;
+    let body = packet.get(35..35 + len).ok_or(PingError::ProtocolError)?;
+    let body = String::from_utf8_lossy(body);
+
+    let fields: Vec<&str> = body.split(';').collect();
+    if fields.len() < 6 {
+        return Err(PingError::ProtocolError);
+    }
+    let field = |i: usize| fields.get(i).filter(|x| !x.is_empty()).map(|x| x.to_string());
+    let number = |i: usize| fields.get(i).and_then(|x| x.parse::<u32>().ok());
+
+    let mut motd = vec![fields[1].to_string()];
+    motd.extend(field(7));
+
+    Ok(BedrockStatus {
+        edition: fields[0].to_string(),
+        motd,
+        protocol: number(2).ok_or(PingError::ProtocolError)?,
+        version: fields[3].to_string(),
+        players: BedrockPlayers {
+            max: number(5).ok_or(PingError::ProtocolError)?,
+            online: number(4).ok_or(PingError::ProtocolError)?,
+        },
+        server_id: field(6).unwrap_or_default(),
+        gamemode: field(8),
+        gamemode_id: number(9),
+        port_v4: fields.get(10).and_then(|x| x.parse().ok()),
+        port_v6: fields.get(11).and_then(|x| x.parse().ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER_ID: &str = "MCPE;Dedicated Server;527;1.19.1;3;20;13253860892328930865;Bedrock level;Survival;1;19132;19133;";
+
+    fn pong(server_id: &str) -> Vec<u8> {
+        let mut packet = vec![UNCONNECTED_PONG];
+        packet.extend_from_slice(&1u64.to_be_bytes());
+        packet.extend_from_slice(&2u64.to_be_bytes());
+        packet.extend_from_slice(&MAGIC);
+        packet.extend_from_slice(&(server_id.len() as u16).to_be_bytes());
+        packet.extend_from_slice(server_id.as_bytes());
+        packet
+    }
+
+    #[test]
+    fn parses_pongs() {
+        let status = parse_pong(&pong(SERVER_ID)).unwrap();
+        assert_eq!(status.edition, "MCPE");
+        assert_eq!(status.motd, ["Dedicated Server", "Bedrock level"]);
+        assert_eq!((status.protocol, status.version.as_str()), (527, "1.19.1"));
+        assert_eq!((status.players.online, status.players.max), (3, 20));
+        assert_eq!(status.gamemode.as_deref(), Some("Survival"));
+        assert_eq!((status.port_v4, status.port_v6), (Some(19132), Some(19133)));
+    }
+
+    #[test]
+    fn parses_pongs_without_optional_fields() {
+        let status = parse_pong(&pong("MCPE;Dedicated Server;527;1.19.1;3;20")).unwrap();
+        assert_eq!(status.motd, ["Dedicated Server"]);
+        assert_eq!(status.server_id, "");
+        assert_eq!(status.gamemode, None);
+    }
+
+    #[test]
+    fn refuses_truncated_pongs() {
+        let packet = pong(SERVER_ID);
+        for len in [0, 1, 17, 33, 34, packet.len() - 1] {
+            assert!(matches!(parse_pong(&packet[..len]), Err(PingError::ProtocolError)), "{}", len);
+        }
+        assert!(matches!(parse_pong(&pong("MCPE;Dedicated Server;527;1.19.1;3")), Err(PingError::ProtocolError)));
+
+        let mut packet = pong(SERVER_ID);
+        packet[0] = UNCONNECTED_PING;
+        assert!(matches!(parse_pong(&packet), Err(PingError::ProtocolError)));
+    }
+}
@@ -0,0 +1,172 @@
+//! Caching successful statuses, so repeated requests don't
+//! ping the same server over and over.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use futures::future::{BoxFuture, FutureExt, Shared};
+use crate::dns::Target;
+use crate::java::Ping;
+use crate::PingError;
+
+/// The outcome of resolving and pinging a server, with the
+/// errors of the program using the cache.
+pub type PingResult<E = PingError> = (Target, Result<Ping, E>);
+
+/// What a cached status depends on: the address as given
+/// and the options that change the server's answer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub host: String,
+    pub port: Option<u16>,
+    pub protocol: i32,
+    pub vhost: Option<String>,
+    pub proxy: Option<String>,
+}
+
+/// A status along with whether it came from the cache.
+#[derive(Debug)]
+pub struct Checked {
+    pub ping: Ping,
+
+    /// When the server was pinged.
+    pub checked_at: SystemTime,
+
+    /// How old the status is, if it came from the cache.
+    pub age: Option<Duration>,
+
+    /// Whether the status is a stale one, served because the
+    /// server couldn't be pinged.
+    pub stale: bool,
+}
+
+/// Normalizes the host of an address for the cache key, so
+/// `Example.com.` and `example.com` share their status.
+pub fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_lowercase()
+}
+
+struct Entry {
+    target: Target,
+    ping: Ping,
+    checked_at: SystemTime,
+    at: Instant,
+}
+
+/// The last successful status of every checked address, and
+/// the pings currently in progress.
+pub struct StatusCache<E = PingError> {
+    entries: Mutex<HashMap<CacheKey, Entry>>,
+    in_flight: Mutex<HashMap<CacheKey, Shared<BoxFuture<'static, PingResult<E>>>>>,
+    ttl: Duration,
+    stale_ttl: Duration,
+    max_entries: usize,
+}
+
+impl<E: Clone + Send + Sync + 'static> StatusCache<E> {
+    /// Creates the cache, keeping statuses for `stale_ttl`
+    /// after their `ttl` to serve when pings fail.
+    pub fn new(ttl: Duration, stale_ttl: Duration, max_entries: usize) -> StatusCache<E> {
+        StatusCache {
+            entries: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            ttl,
+            stale_ttl,
+            max_entries,
+        }
+    }
+
+    /// Returns the status for `key` if it's younger than the
+    /// TTL.
+    pub fn get(&self, key: &CacheKey) -> Option<(Target, Checked)> {
+        self.get_younger(key, self.ttl, false)
+    }
+
+    /// Returns the status for `key` if it's kept as stale
+    /// status, marked as such.
+    pub fn get_stale(&self, key: &CacheKey) -> Option<(Target, Checked)> {
+        self.get_younger(key, self.ttl + self.stale_ttl, true)
+    }
+
+    /// Returns the status for `key` regardless of its age,
+    /// marked as stale if it's older than the TTL.
+    pub fn get_latest(&self, key: &CacheKey) -> Option<(Target, Checked)> {
+        self.get_younger(key, Duration::MAX, false)
+            .map(|(target, checked)| {
+                let stale = checked.age.is_some_and(|x| x >= self.ttl);
+                (target, Checked { stale, ..checked })
+            })
+    }
+
+    fn get_younger(&self, key: &CacheKey, max_age: Duration, stale: bool) -> Option<(Target, Checked)> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(key)
+            .filter(|x| x.at.elapsed() < max_age)
+            .map(|x| (x.target.clone(), Checked { ping: x.ping.clone(), checked_at: x.checked_at, age: Some(x.at.elapsed()), stale }))
+    }
+
+    /// Runs `ping` unless a ping for `key` is in progress
+    /// already, in which case its result is awaited instead,
+    /// so concurrent requests share one connection.
+    pub async fn coalesce<F>(&self, key: &CacheKey, ping: F) -> PingResult<E>
+    where
+        F: Future<Output = PingResult<E>> + Send + 'static,
+    {
+        let shared = self.in_flight.lock().unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| ping.boxed().shared())
+            .clone();
+        let result = shared.clone().await;
+
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.get(key).is_some_and(|x| x.ptr_eq(&shared)) {
+            in_flight.remove(key);
+        }
+        result
+    }
+
+    /// Removes the statuses of `host`, only those of `port` if
+    /// given, and returns how many there were.
+    pub fn remove(&self, host: &str, port: Option<u16>) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let len = entries.len();
+        entries.retain(|key, _| key.host != host || port.is_some_and(|x| key.port != Some(x)));
+        len - entries.len()
+    }
+
+    /// The number of cached statuses, including expired ones
+    /// that haven't been dropped yet.
+    pub fn size(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Removes all statuses and returns how many there were.
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let len = entries.len();
+        entries.clear();
+        len
+    }
+
+    /// Stores a successful status, making room by dropping
+    /// expired entries and then the oldest ones.
+    pub fn insert(&self, key: CacheKey, target: Target, ping: Ping, checked_at: SystemTime) {
+        if self.ttl.is_zero() || self.max_entries == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            entries.retain(|_, x| x.at.elapsed() < self.ttl + self.stale_ttl);
+        }
+        while entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let oldest = entries.iter().min_by_key(|(_, x)| x.at).map(|(k, _)| k.clone());
+            match oldest {
+                Some(oldest) => entries.remove(&oldest),
+                None => break,
+            };
+        }
+        entries.insert(key, Entry { target, ping, checked_at, at: Instant::now() });
+    }
+}
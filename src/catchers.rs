@@ -0,0 +1,41 @@
+//! Errors of requests no route answered, as the same
+//! `Response` the routes respond instead of Rocket's HTML
+//! pages.
+
+use rocket::{Catcher, Request};
+use rocket::http::Status;
+use crate::error::StatusError;
+use crate::format::FormattedOrJson;
+use crate::headers::WithHeaders;
+use crate::ratelimit::RetryAfter;
+use crate::Response;
+
+/// The error of `status`.
+fn error(status: Status) -> StatusError {
+    match status.code {
+        401 => StatusError::Unauthorized,
+        403 => StatusError::Forbidden,
+        404 => StatusError::NotFound,
+        413 => StatusError::TooLarge,
+        429 => StatusError::RateLimited,
+        400..=499 => StatusError::InvalidInput,
+        _ => StatusError::InternalError,
+    }
+}
+
+/// Responds the error of every status, in the format the
+/// request asked for, with `Retry-After` if it was rate
+/// limited.
+#[catch(default)]
+fn default(status: Status, request: &Request<'_>) -> (Status, WithHeaders<FormattedOrJson<Response>>) {
+    let response = WithHeaders::new(FormattedOrJson(Response::error(error(status), None)));
+    let response = match request.local_cache(|| RetryAfter(None)) {
+        RetryAfter(Some(retry_after)) => response.header(RetryAfter::header(*retry_after)),
+        RetryAfter(None) => response,
+    };
+    (status, response)
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![default]
+}
@@ -0,0 +1,70 @@
+//! Changes of the MOTD, version and icon of monitored servers
+//! between their checks, like unannounced updates or hijacked
+//! addresses.
+
+use serde::Serialize;
+use mcstatus_checker::java::StatusResponse;
+
+/// A property of a server that is watched for changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Property {
+    Motd,
+    Version,
+    Icon,
+}
+
+impl Property {
+    /// The name of the property in notifications.
+    pub fn name(self) -> &'static str {
+        match self {
+            Property::Motd => "MOTD",
+            Property::Version => "version",
+            Property::Icon => "icon",
+        }
+    }
+}
+
+/// The watched properties of a server at a check, with its
+/// icon as hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub motd: String,
+    pub version: String,
+    pub icon: Option<String>,
+}
+
+/// The 64 bit FNV-1a hash of `data` in hex, which unlike the
+/// hasher of the standard library stays the same across
+/// releases.
+pub fn hash(data: &str) -> String {
+    let hash = data.bytes().fold(0xcbf29ce484222325u64, |hash, x| (hash ^ x as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+impl Fingerprint {
+    pub fn of(status: &StatusResponse) -> Fingerprint {
+        Fingerprint {
+            motd: status.motd.clone(),
+            version: status.version.name.clone(),
+            icon: status.favicon.as_deref().map(hash),
+        }
+    }
+
+    /// The properties that differ in `new`, with their old and
+    /// new values.
+    pub fn changes(&self, new: &Fingerprint) -> Vec<(Property, String, String)> {
+        let icon = |x: &Option<String>| x.clone().unwrap_or_else(|| "none".to_string());
+        let mut changes = Vec::new();
+        if self.motd != new.motd {
+            changes.push((Property::Motd, self.motd.clone(), new.motd.clone()));
+        }
+        if self.version != new.version {
+            changes.push((Property::Version, self.version.clone(), new.version.clone()));
+        }
+        if self.icon != new.icon {
+            changes.push((Property::Icon, icon(&self.icon), icon(&new.icon)));
+        }
+        changes
+    }
+}
@@ -0,0 +1,399 @@
+//! Minecraft [chat components](https://wiki.vg/Chat), as used
+//! for the MOTD of a server.
+
+use serde_json::{Map, Value};
+use serde::{Deserialize, Serialize};
+
+/// A chat component in any of its JSON forms.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Chat {
+    /// A plain string, possibly containing legacy `§`
+    /// formatting codes.
+    Text(String),
+
+    /// An array, where the first element is the parent of
+    /// the following ones.
+    List(Vec<Chat>),
+
+    /// A component object.
+    Component(Box<Component>),
+}
+
+/// A chat component object with its style and children.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Component {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    /// A translation key, substituted with `with`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub with: Option<Vec<Chat>>,
+
+    /// A color name like `gold` or a `#RRGGBB` hex color.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub italic: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underlined: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strikethrough: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obfuscated: Option<bool>,
+
+    /// Child components, inheriting this component's style.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra: Option<Vec<Chat>>,
+
+    /// Any other fields like `font`, `keybind` or
+    /// `clickEvent`, kept as they were.
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl Default for Chat {
+    fn default() -> Self {
+        Chat::Text(String::new())
+    }
+}
+
+/// The 16 named colors with their legacy code and RGB value.
+const COLORS: [(&str, char, u32); 16] = [
+    ("black", '0', 0x000000),
+    ("dark_blue", '1', 0x0000AA),
+    ("dark_green", '2', 0x00AA00),
+    ("dark_aqua", '3', 0x00AAAA),
+    ("dark_red", '4', 0xAA0000),
+    ("dark_purple", '5', 0xAA00AA),
+    ("gold", '6', 0xFFAA00),
+    ("gray", '7', 0xAAAAAA),
+    ("dark_gray", '8', 0x555555),
+    ("blue", '9', 0x5555FF),
+    ("green", 'a', 0x55FF55),
+    ("aqua", 'b', 0x55FFFF),
+    ("red", 'c', 0xFF5555),
+    ("light_purple", 'd', 0xFF55FF),
+    ("yellow", 'e', 0xFFFF55),
+    ("white", 'f', 0xFFFFFF),
+];
+
+/// The ANSI foreground codes of the named colors, in the
+/// order of `COLORS`.
+const ANSI_COLORS: [u8; 16] = [30, 34, 32, 36, 31, 35, 33, 37, 90, 94, 92, 96, 91, 95, 93, 97];
+
+/// A text color, either one of the named colors or RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Named(usize),
+    Rgb(u32),
+}
+
+impl Color {
+    fn parse(color: &str) -> Option<Color> {
+        if let Some(hex) = color.strip_prefix('#') {
+            return u32::from_str_radix(hex, 16).ok().map(Color::Rgb);
+        }
+        COLORS.iter().position(|x| x.0 == color).map(Color::Named)
+    }
+
+    fn from_code(code: char) -> Option<Color> {
+        COLORS.iter().position(|x| x.1 == code).map(Color::Named)
+    }
+
+    /// The RGB value of the color.
+    pub fn rgb(self) -> u32 {
+        match self {
+            Color::Named(i) => COLORS[i].2,
+            Color::Rgb(rgb) => rgb,
+        }
+    }
+}
+
+/// The resolved style of a piece of text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub color: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underlined: bool,
+    pub strikethrough: bool,
+    pub obfuscated: bool,
+}
+
+impl Style {
+    /// Applies the style of a component on top of the one it
+    /// inherited.
+    fn apply(mut self, component: &Component) -> Style {
+        if let Some(color) = component.color.as_deref().and_then(Color::parse) {
+            self.color = Some(color);
+        }
+        self.bold = component.bold.unwrap_or(self.bold);
+        self.italic = component.italic.unwrap_or(self.italic);
+        self.underlined = component.underlined.unwrap_or(self.underlined);
+        self.strikethrough = component.strikethrough.unwrap_or(self.strikethrough);
+        self.obfuscated = component.obfuscated.unwrap_or(self.obfuscated);
+        self
+    }
+}
+
+/// A run of text sharing the same style.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub text: String,
+    pub style: Style,
+}
+
+impl Chat {
+    /// Resolves the tree into styled runs of text, including
+    /// the legacy `§` codes within the text.
+    pub fn segments(&self) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        self.push_segments(Style::default(), &mut segments);
+        segments
+    }
+
+    fn push_segments(&self, style: Style, segments: &mut Vec<Segment>) {
+        match self {
+            Chat::Text(text) => push_legacy(text, style, segments),
+            Chat::List(list) => {
+                // The first element is the parent of the rest
+                if let Some((first, rest)) = list.split_first() {
+                    let style = match first {
+                        Chat::Component(component) => style.apply(component),
+                        _ => style,
+                    };
+                    first.push_segments(style, segments);
+                    rest.iter().for_each(|x| x.push_segments(style, segments));
+                }
+            }
+            Chat::Component(component) => {
+                let style = style.apply(component);
+                if let Some(text) = &component.text {
+                    push_legacy(text, style, segments);
+                } else if let Some(key) = &component.translate {
+                    push_legacy(&translate(key, component.with.as_deref().unwrap_or_default()), style, segments);
+                }
+                for child in component.extra.iter().flatten() {
+                    child.push_segments(style, segments);
+                }
+            }
+        }
+    }
+
+    /// Renders the MOTD as HTML, with `mc-<color>` and
+    /// `mc-<format>` classes on the spans.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        for segment in self.segments() {
+            let mut classes = Vec::new();
+            let mut css = String::new();
+            match segment.style.color {
+                Some(Color::Named(i)) => classes.push(format!("mc-{}", COLORS[i].0.replace('_', "-"))),
+                Some(Color::Rgb(rgb)) => css = format!(" style=\"color: #{:06x}\"", rgb),
+                None => {}
+            }
+            let style = segment.style;
+            for (enabled, class) in [
+                (style.bold, "mc-bold"),
+                (style.italic, "mc-italic"),
+                (style.underlined, "mc-underlined"),
+                (style.strikethrough, "mc-strikethrough"),
+                (style.obfuscated, "mc-obfuscated"),
+            ] {
+                if enabled {
+                    classes.push(class.to_string());
+                }
+            }
+
+            let text = escape_html(&segment.text).replace('\n', "<br>");
+            if classes.is_empty() && css.is_empty() {
+                html.push_str(&text);
+            } else if classes.is_empty() {
+                html.push_str(&format!("<span{}>{}</span>", css, text));
+            } else {
+                html.push_str(&format!("<span class=\"{}\"{}>{}</span>", classes.join(" "), css, text));
+            }
+        }
+        html
+    }
+
+    /// Renders the MOTD with ANSI escape sequences for
+    /// terminals.
+    pub fn to_ansi(&self) -> String {
+        let mut ansi = String::new();
+        for segment in self.segments() {
+            let style = segment.style;
+            let mut codes = Vec::new();
+            match style.color {
+                Some(Color::Named(i)) => codes.push(ANSI_COLORS[i].to_string()),
+                Some(Color::Rgb(rgb)) => codes.push(format!("38;2;{};{};{}", rgb >> 16, (rgb >> 8) & 0xFF, rgb & 0xFF)),
+                None => {}
+            }
+            for (enabled, code) in [
+                (style.bold, "1"),
+                (style.italic, "3"),
+                (style.underlined, "4"),
+                (style.strikethrough, "9"),
+            ] {
+                if enabled {
+                    codes.push(code.to_string());
+                }
+            }
+
+            if codes.is_empty() {
+                ansi.push_str(&segment.text);
+            } else {
+                ansi.push_str(&format!("\x1b[{}m{}\x1b[0m", codes.join(";"), segment.text));
+            }
+        }
+        ansi
+    }
+
+    /// Concatenates the text of the whole tree, keeping any
+    /// legacy `§` codes.
+    pub fn to_legacy_text(&self) -> String {
+        let mut text = String::new();
+        self.push_text(&mut text);
+        text
+    }
+
+    /// Flattens the tree to plain text without any
+    /// formatting.
+    pub fn to_plain(&self) -> String {
+        strip_codes(&self.to_legacy_text())
+    }
+
+    fn push_text(&self, buffer: &mut String) {
+        match self {
+            Chat::Text(text) => buffer.push_str(text),
+            Chat::List(list) => list.iter().for_each(|x| x.push_text(buffer)),
+            Chat::Component(component) => component.push_text(buffer),
+        }
+    }
+}
+
+impl Component {
+    fn push_text(&self, buffer: &mut String) {
+        if let Some(text) = &self.text {
+            buffer.push_str(text);
+        } else if let Some(key) = &self.translate {
+            buffer.push_str(&translate(key, self.with.as_deref().unwrap_or_default()));
+        }
+        for child in self.extra.iter().flatten() {
+            child.push_text(buffer);
+        }
+    }
+}
+
+/// Substitutes `%s` and `%1$s` in a translation key with the
+/// given arguments. Without the client's language files the
+/// key itself is the best there is.
+fn translate(key: &str, with: &[Chat]) -> String {
+    let mut result = String::new();
+    let mut next = 0;
+    let mut rest = key;
+    while let Some(i) = rest.find('%') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix('%') {
+            result.push('%');
+            rest = after;
+            continue;
+        }
+        let digits = rest.find(|x: char| !x.is_ascii_digit()).unwrap_or(rest.len());
+        let index = match rest[digits..].strip_prefix("$s") {
+            Some(after) if digits > 0 => {
+                let index = rest[..digits].parse::<usize>().unwrap_or(1).saturating_sub(1);
+                rest = after;
+                index
+            }
+            _ => match rest.strip_prefix('s') {
+                Some(after) => {
+                    rest = after;
+                    next += 1;
+                    next - 1
+                }
+                None => {
+                    result.push('%');
+                    continue;
+                }
+            },
+        };
+        if let Some(arg) = with.get(index) {
+            arg.push_text(&mut result);
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Splits text with legacy `§` codes into segments. A color
+/// code resets the formatting, `§r` resets to the style the
+/// text inherited.
+fn push_legacy(text: &str, inherited: Style, segments: &mut Vec<Segment>) {
+    let mut style = inherited;
+    let mut current = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\u{a7}' {
+            current.push(c);
+            continue;
+        }
+        let code = match chars.next() {
+            Some(code) => code.to_ascii_lowercase(),
+            None => break,
+        };
+        if !current.is_empty() {
+            segments.push(Segment { text: std::mem::take(&mut current), style });
+        }
+        match code {
+            'k' => style.obfuscated = true,
+            'l' => style.bold = true,
+            'm' => style.strikethrough = true,
+            'n' => style.underlined = true,
+            'o' => style.italic = true,
+            'r' => style = inherited,
+            code => {
+                if let Some(color) = Color::from_code(code) {
+                    style = Style { color: Some(color), ..Style::default() };
+                }
+            }
+        }
+    }
+    if !current.is_empty() {
+        segments.push(Segment { text: current, style });
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Removes legacy `§` formatting codes.
+pub fn strip_codes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{a7}' {
+            chars.next();
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
@@ -0,0 +1,331 @@
+//! The command line, which serves the HTTP API by default and
+//! can check or watch a server without it.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+use clap::{Args, Parser, Subcommand};
+use hickory_resolver::TokioAsyncResolver;
+use rocket::serde::json::serde_json;
+use tokio::time::MissedTickBehavior;
+use mcstatus_checker::{check_java, dns};
+use mcstatus_checker::cache::Checked;
+use mcstatus_checker::dns::Target;
+use mcstatus_checker::java::Ping;
+use crate::config::Config;
+use crate::error::StatusError;
+use crate::format::V1;
+use crate::{figment, Response};
+
+/// Checks the status of Minecraft servers.
+#[derive(Debug, Parser)]
+#[command(name = "mcstatus-checker", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serves the HTTP API, the same as without a command.
+    Serve,
+
+    /// Pings a Java Edition server once and prints its status,
+    /// exiting with 0 if it is online and 1 if not, or with
+    /// the codes of Nagios plugins.
+    Check(CheckArgs),
+
+    /// Pings a Java Edition server repeatedly and shows its
+    /// latency, players and MOTD, updated in place.
+    Watch(WatchArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    /// The address of the server, like `mc.example.com` or
+    /// `mc.example.com:25566`.
+    pub address: String,
+
+    /// Prints the status as JSON like the `/json` route.
+    #[arg(long, conflicts_with = "nagios")]
+    pub json: bool,
+
+    /// Prints the status like a Nagios plugin, with the latency
+    /// and players as performance data, and exits with its
+    /// codes.
+    #[arg(long)]
+    pub nagios: bool,
+
+    /// The latency in milliseconds from which the server is in
+    /// the warning state.
+    #[arg(long, value_name = "MS", requires = "nagios")]
+    pub warn: Option<u64>,
+
+    /// The latency in milliseconds from which the server is in
+    /// the critical state.
+    #[arg(long, value_name = "MS", requires = "nagios")]
+    pub crit: Option<u64>,
+}
+
+/// The states of a Nagios plugin, which are its exit codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NagiosState {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl NagiosState {
+    fn name(self) -> &'static str {
+        match self {
+            NagiosState::Ok => "OK",
+            NagiosState::Warning => "WARNING",
+            NagiosState::Critical => "CRITICAL",
+            NagiosState::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    /// The address of the server, like `mc.example.com` or
+    /// `mc.example.com:25566`.
+    pub address: String,
+
+    /// The time between two pings, like `5s`, `500ms`, `1m` or
+    /// a number of seconds.
+    #[arg(long, default_value = "5s", value_parser = parse_interval)]
+    pub interval: Duration,
+}
+
+/// The latencies shown in the sparkline of `watch`.
+const SPARKLINE_WIDTH: usize = 60;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Parses a duration of a number with the unit `ms`, `s`, `m`
+/// or `h`, seconds without one.
+fn parse_interval(text: &str) -> Result<Duration, String> {
+    let split = text.find(|x: char| !x.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().map_err(|_| format!("invalid interval `{}`", text))?;
+    let interval = match unit.trim() {
+        "ms" => Duration::from_millis(number),
+        "" | "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(number * 60),
+        "h" => Duration::from_secs(number * 3600),
+        _ => return Err(format!("invalid unit `{}`, expected ms, s, m or h", unit)),
+    };
+    if interval.is_zero() {
+        return Err("the interval has to be longer than 0".to_string());
+    }
+    Ok(interval)
+}
+
+/// Parses the command line, exiting like clap if it is
+/// invalid, except as UNKNOWN for Nagios plugins, which would
+/// take clap's exit code 2 for CRITICAL.
+pub fn parse() -> Result<Cli, ExitCode> {
+    Cli::try_parse().map_err(|e| {
+        if !e.use_stderr() || !std::env::args().any(|x| x == "--nagios") {
+            e.exit();
+        }
+        let message = e.to_string();
+        let message = message.lines().next().unwrap_or_default().trim_start_matches("error: ");
+        println!("MINECRAFT {} - {}", NagiosState::Unknown.name(), message);
+        ExitCode::from(NagiosState::Unknown as u8)
+    })
+}
+
+/// The configuration the server would use and its resolver,
+/// to ping with the same timeouts, proxy and DNS servers.
+fn load() -> Result<(Config, TokioAsyncResolver), String> {
+    let config: Config = figment().extract().map_err(|e| format!("invalid configuration: {}", e))?;
+    let resolver = dns::resolver(&config.dns).map_err(|e| format!("invalid configuration: {}", e))?;
+    Ok((config, resolver))
+}
+
+/// Pings the server of `args` and prints its status, or why it
+/// couldn't be pinged to stderr.
+pub async fn check(args: CheckArgs) -> ExitCode {
+    if let (Some(warn), Some(crit)) = (args.warn, args.crit) {
+        if warn > crit {
+            println!("MINECRAFT {} - --warn {} is above --crit {}", NagiosState::Unknown.name(), warn, crit);
+            return ExitCode::from(NagiosState::Unknown as u8);
+        }
+    }
+    let (config, resolver) = match load() {
+        Ok(x) => x,
+        Err(e) if args.nagios => {
+            println!("MINECRAFT {} - {}", NagiosState::Unknown.name(), e);
+            return ExitCode::from(NagiosState::Unknown as u8);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let result = check_java(&resolver, &args.address, &config.ping_options(), config.timeouts.attempts.max(1)).await.map_err(StatusError::from);
+    let online = result.is_ok();
+    if args.nagios {
+        return nagios(&args, result);
+    }
+    if args.json {
+        let response = match result {
+            Ok((target, ping)) => {
+                let checked = Checked { ping, checked_at: SystemTime::now(), age: None, stale: false };
+                Response::from_checked(checked, Some(target))
+            }
+            Err(e) => Response::error(e, None),
+        };
+        println!("{}", serde_json::to_string_pretty(&V1(&response)).unwrap_or_default());
+    } else {
+        match result {
+            Ok((target, ping)) => {
+                let status = ping.status;
+                println!("{} is online at {}:{}", args.address, target.host, target.port);
+                println!("Version: {} (protocol {})", status.version.name, status.version.protocol);
+                println!("Players: {}/{}", status.players.online, status.players.max);
+                if let Some(latency) = ping.latency {
+                    println!("Latency: {} ms", latency.as_millis());
+                }
+                for (i, line) in status.motd.lines().enumerate() {
+                    println!("{} {}", if i == 0 { "MOTD:" } else { "     " }, line);
+                }
+            }
+            Err(e) => eprintln!("{} can't be pinged: {}", args.address, e),
+        }
+    }
+
+    if online { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+/// Prints the line of a Nagios plugin for `result`, which is
+/// critical if the server can't be pinged and otherwise
+/// depends on the latency thresholds of `args`, and responds
+/// the matching exit code.
+fn nagios(args: &CheckArgs, result: Result<(Target, Ping), StatusError>) -> ExitCode {
+    let state = match &result {
+        Ok((_, ping)) => {
+            let latency = ping.latency.map(|x| x.as_millis() as u64);
+            let exceeds = |threshold: Option<u64>| latency.zip(threshold).is_some_and(|(x, threshold)| x >= threshold);
+            if exceeds(args.crit) {
+                NagiosState::Critical
+            } else if exceeds(args.warn) {
+                NagiosState::Warning
+            } else {
+                NagiosState::Ok
+            }
+        }
+        Err(StatusError::InvalidInput) => NagiosState::Unknown,
+        Err(_) => NagiosState::Critical,
+    };
+
+    match result {
+        Ok((_, ping)) => {
+            let status = ping.status;
+            let threshold = |x: Option<u64>| x.map_or(String::new(), |x| x.to_string());
+            let mut perfdata = format!("players={};;;0;{}", status.players.online, status.players.max);
+            let latency = match ping.latency.map(|x| x.as_millis()) {
+                Some(latency) => {
+                    perfdata = format!("latency={}ms;{};{};0 {}", latency, threshold(args.warn), threshold(args.crit), perfdata);
+                    format!("{} ms", latency)
+                }
+                None => "unknown latency".to_string(),
+            };
+            // A `|` would start the performance data
+            let version = status.version.name.replace('|', "/");
+            println!(
+                "MINECRAFT {} - {} {}, {}/{} players, {} | {}",
+                state.name(), args.address, version, status.players.online, status.players.max, latency, perfdata,
+            );
+        }
+        Err(e) => println!("MINECRAFT {} - {} can't be pinged: {}", state.name(), args.address, e),
+    }
+    ExitCode::from(state as u8)
+}
+
+/// The latencies as a line of bars scaled between the lowest
+/// and highest one, with gaps where the server couldn't be
+/// pinged.
+fn sparkline(latencies: &VecDeque<Option<u64>>) -> String {
+    let min = latencies.iter().flatten().min().copied().unwrap_or(0);
+    let max = latencies.iter().flatten().max().copied().unwrap_or(0);
+    latencies.iter()
+        .map(|x| match x {
+            Some(x) if max > min => SPARKS[((x - min) * (SPARKS.len() as u64 - 1) / (max - min)) as usize],
+            Some(_) => SPARKS[0],
+            None => ' ',
+        })
+        .collect()
+}
+
+/// Redraws the terminal with the last result of `watch` and
+/// the latencies before it.
+fn render(address: &str, interval: Duration, result: &Result<(Target, Ping), StatusError>, latencies: &VecDeque<Option<u64>>) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    // Clears the screen and moves the cursor to the top left
+    write!(out, "\x1b[2J\x1b[H")?;
+    writeln!(out, "Watching {} every {:?}, Ctrl-C to stop", address, interval)?;
+    writeln!(out)?;
+    match result {
+        Ok((target, ping)) => {
+            let status = &ping.status;
+            writeln!(out, "Status:  online at {}:{}", target.host, target.port)?;
+            writeln!(out, "Version: {} (protocol {})", status.version.name, status.version.protocol)?;
+            writeln!(out, "Players: {}/{}", status.players.online, status.players.max)?;
+        }
+        Err(e) => writeln!(out, "Status:  offline, {}", e)?,
+    }
+
+    let measured: Vec<u64> = latencies.iter().flatten().copied().collect();
+    match (measured.iter().min(), measured.iter().max()) {
+        (Some(min), Some(max)) => {
+            let last = latencies.back().copied().flatten().map_or("-".to_string(), |x| format!("{} ms", x));
+            let average = measured.iter().sum::<u64>() / measured.len() as u64;
+            writeln!(out, "Latency: {}, min {} / avg {} / max {} ms", last, min, average, max)?;
+        }
+        _ => writeln!(out, "Latency: -")?,
+    }
+    writeln!(out, "         {}", sparkline(latencies))?;
+
+    if let Ok((_, ping)) = result {
+        for (i, line) in ping.status.motd.lines().enumerate() {
+            writeln!(out, "{} {}", if i == 0 { "MOTD:   " } else { "        " }, line)?;
+        }
+    }
+    out.flush()
+}
+
+/// Pings the server of `args` every interval until stopped,
+/// redrawing the terminal with its status and latencies.
+pub async fn watch(args: WatchArgs) -> ExitCode {
+    let (config, resolver) = match load() {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let options = config.ping_options();
+    let mut latencies = VecDeque::with_capacity(SPARKLINE_WIDTH);
+    let mut interval = tokio::time::interval(args.interval);
+    // Slow pings push the next ones back instead of bunching
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let result = check_java(&resolver, &args.address, &options, config.timeouts.attempts.max(1)).await.map_err(StatusError::from);
+        if latencies.len() == SPARKLINE_WIDTH {
+            latencies.pop_front();
+        }
+        latencies.push_back(result.as_ref().ok().and_then(|(_, ping)| ping.latency).map(|x| x.as_millis() as u64));
+        if let Err(e) = render(&args.address, args.interval, &result, &latencies) {
+            eprintln!("can't write to the terminal: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+}
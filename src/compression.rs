@@ -0,0 +1,125 @@
+//! Compresses responses with Brotli or gzip, as the base64
+//! favicons make JSON statuses large but compress well.
+
+use std::io::{Cursor, Write};
+use flate2::write::GzEncoder;
+use rocket::{Request, Response};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Header};
+use crate::config::Config;
+
+/// The quality of Brotli, fast enough to compress every
+/// response.
+const BROTLI_QUALITY: u32 = 5;
+
+/// The base 2 logarithm of the window size of Brotli.
+const BROTLI_WINDOW: u32 = 22;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// The most preferred encoding of `Accept-Encoding`,
+    /// Brotli if both are preferred the same.
+    fn accepted(accept: &str) -> Option<Encoding> {
+        accept.split(',')
+            .filter_map(|x| {
+                let mut parts = x.split(';').map(str::trim);
+                let encoding = match parts.next()? {
+                    "br" => Encoding::Brotli,
+                    "gzip" | "x-gzip" => Encoding::Gzip,
+                    _ => return None,
+                };
+                let quality = parts.find_map(|x| x.strip_prefix("q=")).map_or(Some(1.0), |x| x.parse::<f32>().ok())?;
+                (quality > 0.0).then_some((encoding, quality))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1).then((a.0 == Encoding::Brotli).cmp(&(b.0 == Encoding::Brotli))))
+            .map(|x| x.0)
+    }
+
+    fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(encoder.into_inner())
+            }
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Whether responses of `content_type` compress well, which
+/// images other than SVG don't.
+fn compressible(content_type: &ContentType) -> bool {
+    let (top, sub) = (content_type.top().as_str(), content_type.sub().as_str());
+    top == "text" || matches!(sub, "json" | "problem+json" | "xml" | "svg+xml" | "yaml" | "x-yaml" | "msgpack" | "atom+xml" | "rss+xml")
+}
+
+/// Compresses the responses of a known size for clients
+/// accepting it. Streamed responses are left as they are so
+/// they keep streaming.
+pub struct Compression;
+
+#[rocket::async_trait]
+impl Fairing for Compression {
+    fn info(&self) -> Info {
+        Info {
+            name: "Compression",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let config = match request.rocket().state::<Config>() {
+            Some(config) if config.compression.enabled => &config.compression,
+            _ => return,
+        };
+        if !response.content_type().is_some_and(|x| compressible(&x)) || response.headers().contains("Content-Encoding") {
+            return;
+        }
+        // The response depends on the header even if it isn't
+        // compressed this time
+        response.adjoin_header(Header::new("Vary", "Accept-Encoding"));
+        let encoding = match request.headers().get_one("Accept-Encoding").and_then(Encoding::accepted) {
+            Some(encoding) => encoding,
+            None => return,
+        };
+        if response.body().preset_size().is_none_or(|x| x < config.min_size) {
+            return;
+        }
+
+        let body = match response.body_mut().to_bytes().await {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("can't read the response to compress it: {}", e);
+                return;
+            }
+        };
+        match encoding.compress(&body) {
+            Ok(compressed) => {
+                response.set_header(Header::new("Content-Encoding", encoding.name()));
+                response.set_sized_body(compressed.len(), Cursor::new(compressed));
+            }
+            Err(e) => {
+                warn!("can't compress the response with {}: {}", encoding.name(), e);
+                response.set_sized_body(body.len(), Cursor::new(body));
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Conditional requests, so polling clients don't download
+//! the same status again, favicon and all.
+
+use std::io::Cursor;
+use rocket::{Request, Response};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Method, Status};
+
+/// Whether `If-None-Match` lists `etag`, compared weakly as
+/// the tags are weak.
+fn matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |x: &str| x.trim().trim_start_matches("W/").to_string();
+    if_none_match.split(',').any(|x| x.trim() == "*" || opaque(x) == opaque(etag))
+}
+
+/// Answers `304 Not Modified` without a body to `GET` and
+/// `HEAD` requests whose `If-None-Match` lists the `ETag` of
+/// the response.
+pub struct ConditionalGet;
+
+#[rocket::async_trait]
+impl Fairing for ConditionalGet {
+    fn info(&self) -> Info {
+        Info {
+            name: "Conditional GET",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if !matches!(request.method(), Method::Get | Method::Head) || response.status() != Status::Ok {
+            return;
+        }
+        let (Some(if_none_match), Some(etag)) = (request.headers().get_one("If-None-Match"), response.headers().get_one("ETag")) else {
+            return;
+        };
+        if matches(if_none_match, etag) {
+            response.set_status(Status::NotModified);
+            response.set_sized_body(0, Cursor::new(""));
+        }
+    }
+}
@@ -0,0 +1,799 @@
+//! The settings of the checker, read from the same sources
+//! as Rocket's own configuration and `mcstatus-checker.toml`.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use mcstatus_checker::dns::DnsConfig;
+use mcstatus_checker::java::PingOptions;
+use mcstatus_checker::proxy::Proxy;
+use crate::cron::Schedule;
+use crate::maintenance::MaintenanceWindow;
+use crate::report;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// How server hostnames are resolved.
+    pub dns: DnsConfig,
+
+    /// How long Java Edition pings wait for each phase.
+    pub timeouts: TimeoutConfig,
+
+    /// Limits of the batch route.
+    pub batch: BatchConfig,
+
+    /// How long statuses are reused.
+    pub cache: CacheConfig,
+
+    /// How long clients and CDNs may reuse the responses.
+    pub cache_control: CacheControlConfig,
+
+    /// How many requests every client IP may make.
+    pub rate_limit: RateLimitConfig,
+
+    /// Which servers may be checked.
+    pub targets: TargetConfig,
+
+    /// How banner images are rendered.
+    pub banner: BannerConfig,
+
+    /// Which servers are checked on a schedule.
+    pub monitor: MonitorConfig,
+
+    /// Where the results of scheduled checks are stored.
+    pub history: HistoryConfig,
+
+    /// The token to authenticate admin routes with as
+    /// `Authorization: Bearer <token>`, which are disabled
+    /// without one.
+    pub admin_token: Option<String>,
+
+    /// The API keys clients may authenticate with as
+    /// `X-API-Key` header, by name.
+    pub api_keys: HashMap<String, ApiKeyConfig>,
+
+    /// Whether requests without an API key are refused.
+    pub require_api_key: bool,
+
+    /// The SOCKS5 proxy Java Edition pings connect through by
+    /// default.
+    pub proxy: Option<Proxy>,
+
+    /// Further proxies, chosen per request with `?proxy=<name>`.
+    pub proxies: HashMap<String, Proxy>,
+
+    /// What is logged and how.
+    pub log: LogConfig,
+
+    /// Where panics and unexpected failures are reported to.
+    pub sentry: Option<SentryConfig>,
+
+    /// The headers protecting browsers showing the responses.
+    pub security_headers: SecurityHeadersConfig,
+
+    /// Which sites may call the routes from browsers.
+    pub cors: CorsConfig,
+
+    /// Which responses are compressed.
+    pub compression: CompressionConfig,
+}
+
+impl Config {
+    /// The default ping options with the configured proxy and
+    /// timeouts, reporting invalid statuses.
+    pub fn ping_options(&self) -> PingOptions {
+        PingOptions {
+            proxy: self.proxy.clone(),
+            connect_timeout: Duration::from_millis(self.timeouts.connect_ms),
+            read_timeout: Duration::from_millis(self.timeouts.read_ms),
+            on_invalid_status: Some(report::protocol_error),
+            ..PingOptions::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Whether responses are compressed with Brotli or gzip
+    /// for clients accepting it.
+    pub enabled: bool,
+
+    /// The least size in bytes of compressed responses.
+    pub min_size: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            enabled: true,
+            min_size: 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// The origins like "https://example.com" scripts may
+    /// call the routes from, "*" for any. Cross-origin
+    /// requests are refused by browsers without any.
+    pub allowed_origins: Vec<String>,
+
+    /// The methods scripts may use.
+    pub allowed_methods: Vec<String>,
+
+    /// The headers scripts may send.
+    pub allowed_headers: Vec<String>,
+
+    /// The headers of responses scripts may read.
+    pub exposed_headers: Vec<String>,
+
+    /// How long browsers may reuse the answer of a preflight
+    /// request in seconds.
+    pub max_age: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        let strings = |x: &[&str]| x.iter().map(|x| x.to_string()).collect();
+        CorsConfig {
+            allowed_origins: Vec::new(),
+            allowed_methods: strings(&["GET", "HEAD", "POST"]),
+            allowed_headers: strings(&["Content-Type", "X-API-Key"]),
+            exposed_headers: strings(&[
+                "X-MC-Online", "X-MC-Players-Online", "X-MC-Players-Max", "X-MC-Latency", "X-Cache",
+                "X-RateLimit-Limit", "X-RateLimit-Remaining", "X-RateLimit-Reset", "Retry-After",
+            ]),
+            max_age: 3600,
+        }
+    }
+}
+
+/// The values of the security headers, of which empty ones
+/// are left out.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SecurityHeadersConfig {
+    /// `X-Content-Type-Options` of every response.
+    pub content_type_options: String,
+
+    /// `Referrer-Policy` of every response.
+    pub referrer_policy: String,
+
+    /// `Content-Security-Policy` of the HTML and SVG
+    /// responses, like the MOTD as HTML and the badges.
+    pub content_security_policy: String,
+
+    /// `Cross-Origin-Resource-Policy` of every response,
+    /// allowing other sites to embed the badges and banners.
+    pub cross_origin_resource_policy: String,
+
+    /// `Strict-Transport-Security` of every response if TLS is
+    /// enabled.
+    pub strict_transport_security: String,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        SecurityHeadersConfig {
+            content_type_options: "nosniff".to_string(),
+            referrer_policy: "no-referrer".to_string(),
+            content_security_policy: "default-src 'none'; style-src 'unsafe-inline'; img-src data:".to_string(),
+            cross_origin_resource_policy: "cross-origin".to_string(),
+            strict_transport_security: "max-age=31536000".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SentryConfig {
+    /// The DSN of the Sentry project.
+    pub dsn: String,
+
+    /// The environment of the reports, like "production".
+    pub environment: Option<String>,
+
+    /// The share of reports to send, between 0 and 1.
+    pub sample_rate: f32,
+
+    /// Whether status responses that couldn't be parsed are
+    /// left out of the reports, as they may contain player
+    /// names. On by default.
+    pub redact_payloads: bool,
+}
+
+impl Default for SentryConfig {
+    fn default() -> Self {
+        SentryConfig {
+            dsn: String::new(),
+            environment: None,
+            sample_rate: 1.0,
+            redact_payloads: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// The least level logged like "info", or directives like
+    /// "warn,mcstatus_checker=debug". `RUST_LOG` takes
+    /// precedence if set.
+    pub level: String,
+
+    /// Whether every line is a JSON object instead of text.
+    pub json: bool,
+
+    /// The OpenTelemetry collector to export the spans of the
+    /// requests and pings to.
+    pub otlp: Option<OtlpConfig>,
+
+    /// The format of the access log written to stdout.
+    pub access: AccessLogFormat,
+}
+
+/// The format of the lines of the access log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    /// No access log.
+    #[default]
+    Off,
+
+    /// The Common Log Format, followed by the checked server,
+    /// the cache status and the time taken.
+    Common,
+
+    /// A JSON object per request.
+    Json,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "info".to_string(),
+            json: false,
+            otlp: None,
+            access: AccessLogFormat::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OtlpConfig {
+    /// The URL traces are sent to with OTLP over HTTP.
+    pub endpoint: String,
+
+    /// The name of the service the spans belong to.
+    pub service_name: String,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        OtlpConfig {
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            service_name: "mcstatus-checker".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    /// How long to wait for the connection to open, in
+    /// milliseconds.
+    pub connect_ms: u64,
+
+    /// How long to wait for the status after connecting, in
+    /// milliseconds.
+    pub read_ms: u64,
+
+    /// The longest timeout requests may ask for with
+    /// `?timeout_ms=`, in milliseconds.
+    pub max_ms: u64,
+
+    /// How long Bedrock Edition pings and queries wait for an
+    /// answer, in milliseconds.
+    pub udp_ms: u64,
+
+    /// How often pings are tried before giving up.
+    pub attempts: usize,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        TimeoutConfig {
+            connect_ms: 3000,
+            read_ms: 3000,
+            max_ms: 10000,
+            udp_ms: 3000,
+            attempts: 3,
+        }
+    }
+}
+
+impl TimeoutConfig {
+    pub fn udp(&self) -> Duration {
+        Duration::from_millis(self.udp_ms)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BatchConfig {
+    /// The most addresses of a batch pinged at once.
+    pub concurrency: usize,
+
+    /// The most addresses accepted in one batch.
+    pub max_size: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            concurrency: 16,
+            max_size: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// How long a successful status is reused in seconds, 0
+    /// to disable the cache.
+    pub ttl: u64,
+
+    /// How long in seconds after the TTL a status is still
+    /// served, marked as stale, when the server can't be
+    /// pinged. 0 to respond the error right away.
+    pub stale_ttl: u64,
+
+    /// The most statuses to keep.
+    pub max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            ttl: 5,
+            stale_ttl: 0,
+            max_entries: 10000,
+        }
+    }
+}
+
+/// The `Cache-Control` headers of the routes, which is
+/// `public, max-age=` the cache TTL if left empty, or
+/// `no-cache` without a cache.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CacheControlConfig {
+    /// The JSON status of online servers.
+    pub json: String,
+
+    /// The SVG badges, whether the server is online or not.
+    pub badge: String,
+
+    /// The PNG banners, whether the server is online or not.
+    pub banner: String,
+
+    /// The icons of online servers, which change less often
+    /// than their status and can be cached for longer.
+    pub icon: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// The average number of requests per minute, 0 to
+    /// disable the limit.
+    pub per_minute: u32,
+
+    /// The number of requests allowed at once.
+    pub burst: u32,
+
+    /// The least number of seconds between two pings of the
+    /// same server, regardless of the client. Requests in
+    /// between get the cached status. 0 to disable the limit.
+    pub target_interval: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            per_minute: 0,
+            burst: 10,
+            target_interval: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TargetConfig {
+    /// Whether servers on loopback, private and link-local
+    /// addresses may be checked, off so a public instance
+    /// can't reach into its own network.
+    pub allow_private: bool,
+
+    /// The hostnames, including their subdomains, and CIDR
+    /// ranges that may be checked. Only these are allowed if
+    /// there are any.
+    pub allow: Vec<String>,
+
+    /// The hostnames and CIDR ranges that may never be
+    /// checked.
+    pub block: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BannerConfig {
+    /// The path of the TrueType font to render the text of
+    /// banners with.
+    pub font: String,
+}
+
+impl Default for BannerConfig {
+    fn default() -> Self {
+        BannerConfig {
+            font: "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// The servers to check.
+    pub servers: Vec<MonitoredServer>,
+
+    /// How many seconds to wait between two checks of every
+    /// server.
+    pub interval: u64,
+
+    /// The number of failed checks in a row after which a
+    /// server is considered offline, unless it has its own.
+    pub down_after: u32,
+
+    /// The number of successful checks in a row after which an
+    /// offline server is considered online again, unless it
+    /// has its own.
+    pub up_after: u32,
+
+    /// How many seconds a server has to be offline for before
+    /// the time between its checks doubles with every check,
+    /// 0 never backs off.
+    pub backoff_after: u64,
+
+    /// The most seconds between two checks of a server that
+    /// is backed off.
+    pub max_backoff: u64,
+
+    /// The URLs to POST a JSON payload to when a monitored
+    /// server goes online or offline.
+    pub webhooks: Vec<String>,
+
+    /// The Telegram bot to message when a monitored server
+    /// goes online or offline.
+    pub telegram: Option<TelegramConfig>,
+
+    /// Notification receivers shared by the monitored servers
+    /// of a group, by name.
+    pub groups: HashMap<String, NotifyConfig>,
+
+    /// Whether to notify when the MOTD, version or icon of a
+    /// server changes.
+    pub notify_changes: bool,
+
+    /// How long to wait on shutdown for the running checks, in
+    /// seconds.
+    pub drain_timeout: u64,
+
+    /// The MQTT broker to publish every check result to.
+    pub mqtt: Option<MqttConfig>,
+
+    /// The texts of notifications.
+    pub templates: TemplateConfig,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        MonitorConfig {
+            servers: Vec::new(),
+            interval: 60,
+            down_after: 1,
+            up_after: 1,
+            backoff_after: 600,
+            max_backoff: 3600,
+            webhooks: Vec::new(),
+            telegram: None,
+            groups: HashMap::new(),
+            notify_changes: true,
+            drain_timeout: 10,
+            mqtt: None,
+            templates: TemplateConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramConfig {
+    /// The token of the bot as given by @BotFather.
+    pub token: String,
+
+    /// The IDs of the chats to send messages to.
+    pub chat_ids: Vec<i64>,
+}
+
+/// The texts of notifications, with placeholders like
+/// `{{server}}` for `server`, `status`, `players_online`,
+/// `players_max`, `version`, `motd`, `latency`, `downtime` and
+/// `uptime`, or `server`, `property`, `old` and `new` for
+/// changes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TemplateConfig {
+    /// The text when a server goes online.
+    pub online: String,
+
+    /// The text when a server goes offline.
+    pub offline: String,
+
+    /// The text when the MOTD, version or icon of a server
+    /// changed.
+    pub changed: String,
+}
+
+impl Default for TemplateConfig {
+    fn default() -> Self {
+        TemplateConfig {
+            online: "{{server}} is online again after {{downtime}}, {{players_online}}/{{players_max}} players".to_string(),
+            offline: "{{server}} is offline, it was online for {{uptime}}".to_string(),
+            changed: "{{server}} changed its {{property}} from {{old}} to {{new}}".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    /// The hostname of the broker.
+    pub host: String,
+
+    pub port: u16,
+
+    /// The client ID to connect with.
+    pub client_id: String,
+
+    /// The username and password to authenticate with, if the
+    /// broker requires them.
+    pub username: Option<String>,
+    pub password: Option<String>,
+
+    /// The first level of the topics, published to as
+    /// `<prefix>/<server>/online` and so on.
+    pub topic_prefix: String,
+
+    /// Whether the broker keeps the last result of every
+    /// topic for new subscribers.
+    pub retain: bool,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        MqttConfig {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "mcstatus-checker".to_string(),
+            username: None,
+            password: None,
+            topic_prefix: "mcstatus".to_string(),
+            retain: true,
+        }
+    }
+}
+
+/// A server to check on a schedule, configured as its
+/// address or as table with further settings.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(from = "MonitoredServerEntry")]
+pub struct MonitoredServer {
+    /// The address to check, in the same form as in routes.
+    pub address: String,
+
+    /// Overrides `interval` of the monitor for this server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+
+    /// When to check the server instead of every interval.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Schedule>,
+
+    /// Overrides the connect and read timeouts for this server,
+    /// in milliseconds up to `timeouts.max_ms`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+
+    /// The protocol version to send in the handshake instead of
+    /// the default one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<i32>,
+
+    /// Text the version name has to contain, checks of other
+    /// versions fail with `UnexpectedVersion`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<String>,
+
+    /// Labels to find the server by.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// The group whose receivers are notified along with the
+    /// server's own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    /// Overrides `down_after` of the monitor for this server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub down_after: Option<u32>,
+
+    /// Overrides `up_after` of the monitor for this server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub up_after: Option<u32>,
+
+    /// The times the server is under maintenance, in which
+    /// nobody is notified and checks don't count towards its
+    /// uptime.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub maintenance: Vec<MaintenanceWindow>,
+
+    /// Who to notify when the server goes online or offline.
+    #[serde(flatten)]
+    pub notify: NotifyConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MonitoredServerEntry {
+    Address(String),
+    // Boxed, as it is much larger than an address
+    Server(Box<ServerEntry>),
+}
+
+#[derive(Deserialize)]
+struct ServerEntry {
+    address: String,
+    #[serde(default)]
+    interval: Option<u64>,
+    #[serde(default)]
+    schedule: Option<Schedule>,
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+    #[serde(default)]
+    protocol: Option<i32>,
+    #[serde(default)]
+    expected_version: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default)]
+    down_after: Option<u32>,
+    #[serde(default)]
+    up_after: Option<u32>,
+    #[serde(default)]
+    maintenance: Vec<MaintenanceWindow>,
+    #[serde(flatten)]
+    notify: NotifyConfig,
+}
+
+impl From<MonitoredServerEntry> for MonitoredServer {
+    fn from(entry: MonitoredServerEntry) -> Self {
+        match entry {
+            MonitoredServerEntry::Address(address) => MonitoredServer {
+                address,
+                interval: None,
+                schedule: None,
+                timeout_ms: None,
+                protocol: None,
+                expected_version: None,
+                tags: Vec::new(),
+                group: None,
+                down_after: None,
+                up_after: None,
+                maintenance: Vec::new(),
+                notify: NotifyConfig::default(),
+            },
+            MonitoredServerEntry::Server(entry) => {
+                let ServerEntry { address, interval, schedule, timeout_ms, protocol, expected_version, tags, group, down_after, up_after, maintenance, notify } = *entry;
+                MonitoredServer { address, interval, schedule, timeout_ms, protocol, expected_version, tags, group, down_after, up_after, maintenance, notify }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    /// The URLs to POST a JSON payload to, besides the ones of
+    /// the monitor.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<String>,
+
+    /// The Discord webhook URLs to post an embed to.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub discord: Vec<String>,
+
+    /// The Slack incoming webhook URLs to post a message to.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub slack: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// The path of the SQLite database, created if it doesn't
+    /// exist. Only opened if there are servers to monitor or
+    /// an admin token to add them with.
+    pub path: String,
+
+    /// How many days to keep every check result for, 0 to
+    /// keep them forever. Older ones are downsampled.
+    pub retention_days: u64,
+
+    /// The minutes of older check results to average into
+    /// one, 0 to remove them instead.
+    pub downsample_minutes: u64,
+
+    /// How many days to keep downsampled results for, 0 to
+    /// keep them forever.
+    pub downsampled_retention_days: u64,
+
+    /// How many minutes a player can be missing from the
+    /// sample of a server with more players than it lists
+    /// before their session is ended.
+    pub session_timeout_minutes: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            path: "history.sqlite".to_string(),
+            retention_days: 7,
+            downsample_minutes: 5,
+            downsampled_retention_days: 90,
+            session_timeout_minutes: 15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ApiKeyConfig {
+    /// The secret clients send.
+    pub key: String,
+
+    /// The average number of requests per minute with this
+    /// key, 0 to disable the limit. Requests with a key are
+    /// not limited per client IP.
+    pub per_minute: u32,
+
+    /// The number of requests allowed at once.
+    pub burst: u32,
+}
+
+impl Default for ApiKeyConfig {
+    fn default() -> Self {
+        ApiKeyConfig {
+            key: String::new(),
+            per_minute: 0,
+            burst: 10,
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Cross-origin resource sharing, so status widgets on other
+//! sites can call the routes from browsers.
+
+use std::path::PathBuf;
+use rocket::{Request, Response, Route};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+use crate::config::{Config, CorsConfig};
+
+/// Whether `origin` may call the routes.
+fn allowed(config: &CorsConfig, origin: &str) -> bool {
+    config.allowed_origins.iter().any(|x| x == "*" || x.eq_ignore_ascii_case(origin))
+}
+
+/// Answers preflight requests of every route, the fairing
+/// adds the headers allowing them.
+#[options("/<_path..>", rank = 100)]
+fn preflight(_path: PathBuf) -> Status {
+    Status::NoContent
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![preflight]
+}
+
+/// Allows the configured origins to read the responses and
+/// answers their preflight requests.
+pub struct Cors;
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let config = match request.rocket().state::<Config>() {
+            Some(config) => &config.cors,
+            None => return,
+        };
+        if config.allowed_origins.is_empty() {
+            return;
+        }
+        // Responses differ by origin, which caches have to know
+        response.adjoin_header(Header::new("Vary", "Origin"));
+        let origin = match request.headers().get_one("Origin").filter(|x| allowed(config, x)) {
+            Some(origin) => origin,
+            None => return,
+        };
+
+        response.set_header(Header::new("Access-Control-Allow-Origin", origin.to_string()));
+        if request.method() == Method::Options && request.headers().contains("Access-Control-Request-Method") {
+            response.set_header(Header::new("Access-Control-Allow-Methods", config.allowed_methods.join(", ")));
+            response.set_header(Header::new("Access-Control-Allow-Headers", config.allowed_headers.join(", ")));
+            response.set_header(Header::new("Access-Control-Max-Age", config.max_age.to_string()));
+        } else if !config.exposed_headers.is_empty() {
+            response.set_header(Header::new("Access-Control-Expose-Headers", config.exposed_headers.join(", ")));
+        }
+    }
+}
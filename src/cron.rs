@@ -0,0 +1,209 @@
+//! Cron expressions telling when a monitored server is
+//! checked, instead of every interval.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::date;
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A cron expression of the five fields minute, hour, day of
+/// the month, month and day of the week in UTC, like
+/// `*/15 * * * *`, or one of `@hourly`, `@daily`, `@weekly`,
+/// `@monthly` and `@yearly`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Schedule {
+    expression: String,
+
+    /// The matching values of every field as bits.
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+
+    /// Whether the day of the month or of the week is
+    /// restricted, in which case either of them matching is
+    /// enough if both are, like in crontab.
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+/// Parses one field of values between `min` and `max` into
+/// bits, accepting `*`, values, ranges like `1-5`, steps like
+/// `*/15` or `0-30/5`, lists of them and the `names` of the
+/// values starting at `min`.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |x: &str| {
+        let lower = x.to_ascii_lowercase();
+        match names.iter().position(|name| *name == lower) {
+            Some(position) => Ok(min + position as u32),
+            None => x.parse::<u32>().ok().filter(|x| (min..=max).contains(x)).ok_or_else(|| format!("invalid value `{}`", x)),
+        }
+    };
+
+    let mut bits = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|x| *x > 0).ok_or_else(|| format!("invalid step `{}`", step))?),
+            None => (item, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            // A single value with a step runs until the maximum
+            None if item.contains('/') => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+        if start > end {
+            return Err(format!("invalid range `{}`", range));
+        }
+        bits |= (start..=end).step_by(step as usize).fold(0, |bits, x| bits | 1 << x);
+    }
+    Ok(bits)
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = String;
+
+    fn try_from(expression: String) -> Result<Self, Self::Error> {
+        let fields = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            x => x,
+        };
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("invalid cron expression `{}`, expected five fields", expression));
+        };
+
+        let invalid = |e: String| format!("invalid cron expression `{}`: {}", expression, e);
+        let mut weekday_bits = parse_field(weekdays, 0, 7, &WEEKDAYS).map_err(invalid)?;
+        // Sunday is both 0 and 7
+        if weekday_bits & 1 << 7 != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+        Ok(Schedule {
+            minutes: parse_field(minutes, 0, 59, &[]).map_err(invalid)?,
+            hours: parse_field(hours, 0, 23, &[]).map_err(invalid)?,
+            days: parse_field(days, 1, 31, &[]).map_err(invalid)?,
+            months: parse_field(months, 1, 12, &MONTHS).map_err(invalid)?,
+            weekdays: weekday_bits,
+            days_restricted: days != "*",
+            weekdays_restricted: weekdays != "*",
+            expression,
+        })
+    }
+}
+
+impl From<Schedule> for String {
+    fn from(schedule: Schedule) -> Self {
+        schedule.expression
+    }
+}
+
+impl Schedule {
+    /// Whether the minute of `at` matches.
+    pub fn matches(&self, at: SystemTime) -> bool {
+        let secs = at.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs());
+        let days = (secs / 86400) as i64;
+        let (_, month, day) = date::civil(days);
+        let bit = |bits: u64, x: u64| bits & 1 << x != 0;
+
+        let day_matches = match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => bit(self.days, day as u64) || bit(self.weekdays, date::weekday(days) as u64),
+            _ => bit(self.days, day as u64) && bit(self.weekdays, date::weekday(days) as u64),
+        };
+        bit(self.minutes, secs / 60 % 60) && bit(self.hours, secs / 3600 % 24) && bit(self.months, month as u64) && day_matches
+    }
+}
+
+/// The time from `at` until the next minute starts.
+pub fn until_next_minute(at: SystemTime) -> Duration {
+    let secs = at.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs());
+    Duration::from_secs(60 - secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Monday, 1 January 2024 in days since the Unix epoch.
+    const MONDAY: u64 = 19723;
+
+    fn at(days: u64, hour: u64, minute: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(days * 86400 + hour * 3600 + minute * 60)
+    }
+
+    fn schedule(expression: &str) -> Schedule {
+        Schedule::try_from(expression.to_string()).unwrap()
+    }
+
+    #[test]
+    fn matches_steps() {
+        let schedule = schedule("*/15 * * * *");
+        for minute in [0, 15, 30, 45] {
+            assert!(schedule.matches(at(MONDAY, 12, minute)));
+        }
+        assert!(!schedule.matches(at(MONDAY, 12, 10)));
+
+        let schedule = self::schedule("0-30/10 * * * *");
+        assert!(schedule.matches(at(MONDAY, 0, 30)));
+        assert!(!schedule.matches(at(MONDAY, 0, 40)));
+    }
+
+    #[test]
+    fn matches_ranges_and_lists() {
+        let schedule = schedule("0 9-17 * * *");
+        assert!(schedule.matches(at(MONDAY, 9, 0)));
+        assert!(schedule.matches(at(MONDAY, 17, 0)));
+        assert!(!schedule.matches(at(MONDAY, 18, 0)));
+        assert!(!schedule.matches(at(MONDAY, 9, 1)));
+
+        let schedule = self::schedule("0,30 0 * jan,jul *");
+        assert!(schedule.matches(at(MONDAY, 0, 30)));
+        assert!(!schedule.matches(at(MONDAY + 31, 0, 30)));
+    }
+
+    #[test]
+    fn matches_days() {
+        let weekdays = schedule("0 0 * * mon-fri");
+        assert!(weekdays.matches(at(MONDAY, 0, 0)));
+        assert!(!weekdays.matches(at(MONDAY + 6, 0, 0)));
+
+        // Sunday is 7 as well as 0
+        assert!(schedule("0 0 * * 7").matches(at(MONDAY + 6, 0, 0)));
+        assert!(schedule("0 0 * * 0").matches(at(MONDAY + 6, 0, 0)));
+
+        // Either day matching is enough if both are restricted
+        let friday_13th = schedule("0 0 13 * fri");
+        assert!(!friday_13th.matches(at(MONDAY, 0, 0)));
+        assert!(friday_13th.matches(at(MONDAY + 4, 0, 0)));
+        assert!(friday_13th.matches(at(MONDAY + 12, 0, 0)));
+
+        // Otherwise both have to
+        let first = schedule("0 0 1 * *");
+        assert!(first.matches(at(MONDAY, 0, 0)));
+        assert!(!first.matches(at(MONDAY + 1, 0, 0)));
+    }
+
+    #[test]
+    fn expands_shortcuts() {
+        let daily = schedule("@daily");
+        assert!(daily.matches(at(MONDAY, 0, 0)));
+        assert!(!daily.matches(at(MONDAY, 1, 0)));
+        assert_eq!(String::from(daily), "@daily");
+    }
+
+    #[test]
+    fn refuses_invalid_expressions() {
+        for expression in ["* * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "5-1 * * * *", "*/0 * * * *", "* * * foo *", "@often"] {
+            assert!(Schedule::try_from(expression.to_string()).is_err(), "{}", expression);
+        }
+    }
+}
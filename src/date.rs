@@ -0,0 +1,23 @@
+//! Calendar dates of days since the Unix epoch, in UTC.
+
+/// The year, month and day of the month of `days` since the
+/// Unix epoch, after Howard Hinnant's `civil_from_days`.
+pub fn civil(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month as u32, day as u32)
+}
+
+/// The day of the week of `days` since the Unix epoch, from 0
+/// for Sunday to 6 for Saturday.
+pub fn weekday(days: i64) -> u32 {
+    // The Unix epoch was a Thursday
+    (days + 4).rem_euclid(7) as u32
+}
@@ -0,0 +1,106 @@
+//! Discord webhook messages about monitored servers going
+//! online or offline, as embeds with the server icon.
+
+use reqwest::multipart::{Form, Part};
+use rocket::serde::json::serde_json;
+use serde::Serialize;
+use crate::favicon;
+use crate::notify::{self, StatusChange};
+
+/// The green of Minecraft's chat colors.
+const ONLINE_COLOR: u32 = 0x55ff55;
+
+/// The red of Minecraft's chat colors.
+const OFFLINE_COLOR: u32 = 0xff5555;
+
+#[derive(Debug, Serialize)]
+struct Message {
+    content: String,
+    embeds: Vec<Embed>,
+}
+
+#[derive(Debug, Serialize)]
+struct Embed {
+    title: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+
+    color: u32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail: Option<Thumbnail>,
+
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, Serialize)]
+struct Thumbnail {
+    url: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Field {
+    name: String,
+    value: String,
+    inline: bool,
+}
+
+impl Field {
+    fn new(name: &str, value: String) -> Field {
+        Field { name: name.to_string(), value, inline: true }
+    }
+}
+
+/// Builds the message about `change` with `text` above the
+/// embed, and the PNG of the server icon it refers to as
+/// attachment, if there is one.
+fn message(change: &StatusChange, text: &str) -> (Message, Option<Vec<u8>>) {
+    let status = change.ping.as_ref().map(|x| &x.status);
+    let icon = status.and_then(|x| x.favicon.as_deref()).and_then(favicon::decode_png);
+
+    let mut fields = Vec::new();
+    if let Some(status) = status {
+        fields.push(Field::new("Players", format!("{}/{}", status.players.online, status.players.max)));
+        fields.push(Field::new("Version", status.version.name.clone()));
+    }
+    let duration = notify::format_duration(change.previous_duration);
+    if change.online() {
+        fields.push(Field::new("Downtime", duration));
+    } else {
+        fields.push(Field::new("Was online for", duration));
+    }
+
+    let embed = Embed {
+        title: format!("{} is {}", change.server, if change.online() { "online" } else { "offline" }),
+        description: status.map(|x| x.motd.clone()).filter(|x| !x.is_empty()),
+        color: if change.online() { ONLINE_COLOR } else { OFFLINE_COLOR },
+        thumbnail: icon.as_ref().map(|_| Thumbnail { url: "attachment://icon.png".to_string() }),
+        fields,
+    };
+    (Message { content: text.to_string(), embeds: vec![embed] }, icon)
+}
+
+/// Posts the message about `change` with `text` to the
+/// webhook at `url`, uploading the server icon along with it.
+pub async fn send(client: &reqwest::Client, url: &str, change: &StatusChange, text: &str) -> Result<(), reqwest::Error> {
+    let (message, icon) = message(change, text);
+    let request = match icon {
+        Some(icon) => {
+            let form = Form::new()
+                .text("payload_json", serde_json::to_string(&message).unwrap_or_default())
+                .part("files[0]", Part::bytes(icon).file_name("icon.png").mime_str("image/png")?);
+            client.post(url).multipart(form)
+        }
+        None => client.post(url).json(&message),
+    };
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
+
+/// Posts `text` alone to the webhook at `url`.
+pub async fn send_text(client: &reqwest::Client, url: &str, text: &str) -> Result<(), reqwest::Error> {
+    let message = Message { content: text.to_string(), embeds: Vec::new() };
+    client.post(url).json(&message).send().await?.error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,222 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use hickory_resolver::TokioAsyncResolver;
+use hickory_resolver::config::{NameServerConfig, Protocol, ResolverConfig, ResolverOpts};
+use hickory_resolver::system_conf::read_system_conf;
+use hickory_resolver::error::ResolveErrorKind;
+use serde::{Deserialize, Serialize};
+use crate::proxy::Proxy;
+use crate::PingError;
+
+/// The host and port a status check actually connects to.
+#[derive(Debug, Clone, Serialize)]
+pub struct Target {
+    /// The hostname or IP literal that was connected to.
+    pub host: String,
+
+    /// The port that was connected to.
+    pub port: u16,
+
+    /// Whether the target was taken from a `_minecraft._tcp`
+    /// SRV record.
+    pub srv: bool,
+
+    /// The name of the SRV record that was followed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub srv_record: Option<String>,
+
+    /// The IP address `host` resolved to, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<IpAddr>,
+
+    /// Why `host` couldn't be resolved, if it couldn't.
+    #[serde(skip)]
+    pub unresolved: Option<PingError>,
+}
+
+impl Target {
+    /// The host to open the connection to, the resolved IP
+    /// if there is one so that the reported one is used.
+    pub fn connect_host(&self) -> String {
+        self.ip.map(|x| x.to_string()).unwrap_or_else(|| self.host.clone())
+    }
+
+    /// The host to open a TCP connection to, the hostname if
+    /// it goes through a proxy, which resolves it itself, and
+    /// otherwise the [`connect_host`](Target::connect_host).
+    pub fn tcp_host(&self, proxy: Option<&Proxy>) -> String {
+        match proxy {
+            Some(_) => self.host.clone(),
+            None => self.connect_host(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DnsConfig {
+    /// The upstream DNS servers as `ip` or `ip:port`, used
+    /// instead of the system configuration if not empty.
+    pub servers: Vec<String>,
+
+    /// Resolves with DNS-over-HTTPS, either through one of the
+    /// providers "cloudflare", "google" and "quad9", or
+    /// through the `servers` with this hostname.
+    pub doh: Option<String>,
+
+    /// The number of answers to cache.
+    pub cache_size: usize,
+
+    /// The longest time in seconds to cache an answer for,
+    /// regardless of its TTL.
+    pub max_ttl: u64,
+
+    /// The name `/readyz` resolves to tell whether DNS works.
+    pub health_check: String,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        DnsConfig {
+            servers: Vec::new(),
+            doh: None,
+            cache_size: 1024,
+            max_ttl: 300,
+            health_check: "minecraft.net".to_string(),
+        }
+    }
+}
+
+/// How long to wait for each configured server to answer.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for a lookup as a whole, including all
+/// servers and retries, so unreachable servers don't stall
+/// every status check.
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Creates the resolver for the configured DNS-over-HTTPS or
+/// plain upstream servers, or from the system configuration
+/// if there are none, falling back to the default upstream
+/// servers if it can't be read.
+///
+/// Answers are cached for their TTL, but no longer than the
+/// configured maximum.
+pub fn resolver(config: &DnsConfig) -> Result<TokioAsyncResolver, String> {
+    let (resolver_config, mut options) = if let Some(doh) = &config.doh {
+        let resolver_config = match doh.as_str() {
+            "cloudflare" => ResolverConfig::cloudflare_https(),
+            "google" => ResolverConfig::google_https(),
+            "quad9" => ResolverConfig::quad9_https(),
+            name => {
+                let mut resolver_config = ResolverConfig::new();
+                for addr in parse_servers(&config.servers, 443)? {
+                    let mut server = NameServerConfig::new(addr, Protocol::Https);
+                    server.tls_dns_name = Some(name.to_string());
+                    resolver_config.add_name_server(server);
+                }
+                if resolver_config.name_servers().is_empty() {
+                    return Err(format!("no DNS servers configured for `{}`", name));
+                }
+                resolver_config
+            }
+        };
+        (resolver_config, lookup_options())
+    } else if config.servers.is_empty() {
+        read_system_conf().unwrap_or_default()
+    } else {
+        let mut resolver_config = ResolverConfig::new();
+        for addr in parse_servers(&config.servers, 53)? {
+            resolver_config.add_name_server(NameServerConfig::new(addr, Protocol::Udp));
+            resolver_config.add_name_server(NameServerConfig::new(addr, Protocol::Tcp));
+        }
+        (resolver_config, lookup_options())
+    };
+
+    options.cache_size = config.cache_size;
+    options.positive_max_ttl = Some(Duration::from_secs(config.max_ttl));
+    options.negative_max_ttl = Some(Duration::from_secs(config.max_ttl));
+    Ok(TokioAsyncResolver::tokio(resolver_config, options))
+}
+
+fn lookup_options() -> ResolverOpts {
+    let mut options = ResolverOpts::default();
+    options.timeout = LOOKUP_TIMEOUT;
+    options
+}
+
+/// Parses servers given as `ip`, `ip:port` or `[ipv6]:port`,
+/// defaulting to `port`.
+fn parse_servers(servers: &[String], port: u16) -> Result<Vec<SocketAddr>, String> {
+    servers.iter()
+        .map(|server| server.parse::<SocketAddr>().ok()
+            .or_else(|| server.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, port)))
+            .ok_or_else(|| format!("invalid DNS server `{}`", server)))
+        .collect()
+}
+
+/// Resolves the address to connect to for `host`.
+///
+/// Like the vanilla client, the SRV record is only looked
+/// up when no explicit port was given. If there is no SRV
+/// record, the host itself is used. The resulting host is
+/// then looked up for its IP address.
+pub async fn resolve(resolver: &TokioAsyncResolver, host: &str, port: Option<u16>) -> Target {
+    let mut target = Target {
+        host: host.to_string(),
+        port: port.unwrap_or(25565),
+        srv: false,
+        srv_record: None,
+        ip: None,
+        unresolved: None,
+    };
+
+    if port.is_none() && host.parse::<IpAddr>().is_err() {
+        if let Some(srv) = lookup_srv(resolver, host).await {
+            target = srv;
+        }
+    }
+
+    match lookup_ip(resolver, &target.host).await {
+        Ok(ip) => target.ip = Some(ip),
+        Err(e) => target.unresolved = Some(e),
+    }
+    target
+}
+
+async fn lookup_srv(resolver: &TokioAsyncResolver, host: &str) -> Option<Target> {
+    let name = format!("_minecraft._tcp.{}", host.trim_end_matches('.'));
+    let lookup = tokio::time::timeout(RESOLVE_TIMEOUT, resolver.srv_lookup(format!("{}.", name))).await.ok()?.ok()?;
+    let record = lookup.iter()
+        .min_by_key(|srv| (srv.priority(), std::cmp::Reverse(srv.weight())))?;
+
+    Some(Target {
+        host: record.target().to_utf8().trim_end_matches('.').to_string(),
+        port: record.port(),
+        srv: true,
+        srv_record: Some(name),
+        ip: None,
+        unresolved: None,
+    })
+}
+
+/// Whether `name` can be resolved, to tell whether DNS
+/// works.
+pub async fn healthy(resolver: &TokioAsyncResolver, name: &str) -> bool {
+    lookup_ip(resolver, name).await.is_ok()
+}
+
+/// Looks up the first address of `host`, failing with
+/// `UnknownHost` if the domain doesn't exist or has no
+/// addresses and `DnsError` if it couldn't be looked up.
+async fn lookup_ip(resolver: &TokioAsyncResolver, host: &str) -> Result<IpAddr, PingError> {
+    if let Ok(ip) = host.parse() {
+        return Ok(ip);
+    }
+    let lookup = resolver.lookup_ip(format!("{}.", host.trim_end_matches('.')));
+    match tokio::time::timeout(RESOLVE_TIMEOUT, lookup).await {
+        Ok(Ok(lookup)) => lookup.iter().next().ok_or(PingError::UnknownHost),
+        Ok(Err(e)) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => Err(PingError::UnknownHost),
+        Ok(Err(_)) | Err(_) => Err(PingError::DnsError),
+    }
+}
@@ -0,0 +1,147 @@
+//! The errors of the routes, which are those of pinging a
+//! server and those only the server has.
+
+use serde::{Deserialize, Serialize, Serializer};
+use thiserror::Error;
+use mcstatus_checker::PingError;
+
+/// Why a request failed. Serialized as an object of its
+/// [`code`](StatusError::code), message and more specific
+/// cause.
+#[derive(Error, Debug, Clone, Deserialize)]
+pub enum StatusError {
+    #[error("Protocol error")]
+    ProtocolError,
+
+    #[error("The server responded a status that isn't valid JSON")]
+    MalformedJson,
+
+    #[error("The server responded an unexpected packet")]
+    UnexpectedPacket,
+
+    #[error("The connection was closed before the status was read")]
+    ConnectionReset,
+
+    #[error("The domain doesn't exist or has no address")]
+    UnknownHost,
+
+    #[error("The domain couldn't be resolved")]
+    DnsError,
+
+    #[error("The server refused the connection, it is likely offline")]
+    ConnectionRefused,
+
+    #[error("The network of the server can't be reached")]
+    Unreachable,
+
+    #[error("Invalid input string")]
+    InvalidInput,
+
+    #[error("Timed out")]
+    Timeout,
+
+    #[error("Timed out connecting")]
+    ConnectTimeout,
+
+    #[error("Timed out waiting for the status")]
+    ReadTimeout,
+
+    #[error("The server was pinged too recently")]
+    RateLimited,
+
+    #[error("The server is not allowed to be checked")]
+    Forbidden,
+
+    #[error("The server is not monitored")]
+    NotMonitored,
+
+    #[error("The history couldn't be read")]
+    HistoryError,
+
+    #[error("The server is already monitored")]
+    AlreadyMonitored,
+
+    #[error("The server is configured and can't be changed at runtime")]
+    Configured,
+
+    #[error("No monitored server has the tag")]
+    UnknownTag,
+
+    #[error("The server runs another version than expected")]
+    UnexpectedVersion,
+
+    #[error("The configuration is invalid")]
+    InvalidConfig,
+
+    #[error("No route matches the request")]
+    NotFound,
+
+    #[error("A valid API key or admin token is required")]
+    Unauthorized,
+
+    #[error("The request body is too large")]
+    TooLarge,
+
+    #[error("Something went wrong while responding")]
+    InternalError,
+}
+
+impl StatusError {
+    /// The error clients tell apart, which the more specific
+    /// causes of protocol errors are responded as by version 1.
+    /// Only `/v2` and problems tell an unknown host from a
+    /// server that is offline, by their cause.
+    pub fn code(&self) -> StatusError {
+        match self {
+            StatusError::MalformedJson | StatusError::UnexpectedPacket | StatusError::ConnectionReset
+            | StatusError::UnknownHost | StatusError::DnsError | StatusError::ConnectionRefused | StatusError::Unreachable => StatusError::ProtocolError,
+            x => x.clone(),
+        }
+    }
+
+    /// The name of the error, if it is more specific than its
+    /// code.
+    pub fn cause(&self) -> Option<String> {
+        let (name, code) = (format!("{:?}", self), format!("{:?}", self.code()));
+        (name != code).then_some(name)
+    }
+}
+
+impl From<PingError> for StatusError {
+    fn from(e: PingError) -> Self {
+        match e {
+            PingError::ProtocolError => StatusError::ProtocolError,
+            PingError::MalformedJson => StatusError::MalformedJson,
+            PingError::UnexpectedPacket => StatusError::UnexpectedPacket,
+            PingError::ConnectionReset => StatusError::ConnectionReset,
+            PingError::UnknownHost => StatusError::UnknownHost,
+            PingError::DnsError => StatusError::DnsError,
+            PingError::ConnectionRefused => StatusError::ConnectionRefused,
+            PingError::Unreachable => StatusError::Unreachable,
+            PingError::InvalidInput => StatusError::InvalidInput,
+            PingError::Timeout => StatusError::Timeout,
+            PingError::ConnectTimeout => StatusError::ConnectTimeout,
+            PingError::ReadTimeout => StatusError::ReadTimeout,
+        }
+    }
+}
+
+/// The fields of a serialized [`StatusError`].
+#[derive(Serialize)]
+struct ErrorFields {
+    code: String,
+    message: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cause: Option<String>,
+}
+
+impl Serialize for StatusError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ErrorFields {
+            code: format!("{:?}", self.code()),
+            message: self.to_string(),
+            cause: self.cause(),
+        }.serialize(serializer)
+    }
+}
@@ -0,0 +1,22 @@
+//! The server icon, sent as a base64 data URI in the status.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use image::ImageFormat;
+
+/// Decodes the PNG of a `data:image/png;base64,` favicon.
+/// Line breaks within the base64, which some servers send,
+/// are skipped.
+pub fn decode(favicon: &str) -> Option<Vec<u8>> {
+    let data = favicon.strip_prefix("data:image/png;base64,")?;
+    let data: String = data.chars().filter(|x| !x.is_ascii_whitespace()).collect();
+    STANDARD.decode(data).ok()
+}
+
+/// Like `decode`, but only if the data actually is an image
+/// in the PNG format.
+pub fn decode_png(favicon: &str) -> Option<Vec<u8>> {
+    let png = decode(favicon)?;
+    image::load_from_memory_with_format(&png, ImageFormat::Png).ok()?;
+    Some(png)
+}
@@ -0,0 +1,77 @@
+//! Atom feeds of monitored servers going offline and online
+//! again, generated from their incidents.
+
+use std::cmp::Reverse;
+use std::time::Duration;
+use quick_xml::escape::escape;
+use rocket::http::ContentType;
+use crate::date;
+use crate::history::Incident;
+use crate::notify;
+
+/// The content type of Atom feeds.
+pub fn content_type() -> ContentType {
+    ContentType::new("application", "atom+xml")
+}
+
+/// Formats seconds since the Unix epoch as RFC 3339 timestamp
+/// in UTC, as Atom requires.
+fn rfc3339(secs: u64) -> String {
+    let (days, time) = ((secs / 86400) as i64, secs % 86400);
+    let (year, month, day) = date::civil(days);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+struct Entry {
+    id: String,
+    title: String,
+    summary: String,
+    updated: u64,
+}
+
+/// The entries of an incident of `server`, one for going
+/// offline and one for going online again if it did.
+fn entries(server: &str, incident: &Incident) -> Vec<Entry> {
+    let mut entries = vec![Entry {
+        id: format!("urn:mcstatus-checker:incident:{}:start", incident.id),
+        title: format!("{} is offline", server),
+        summary: match &incident.error {
+            Some(error) => format!("{} couldn't be pinged: {}", server, error),
+            None => format!("{} couldn't be pinged", server),
+        },
+        updated: incident.start,
+    }];
+    if let Some(end) = incident.end {
+        entries.push(Entry {
+            id: format!("urn:mcstatus-checker:incident:{}:end", incident.id),
+            title: format!("{} is online again", server),
+            summary: format!("{} is online again after {}", server, notify::format_duration(Duration::from_secs(incident.duration))),
+            updated: end,
+        });
+    }
+    entries
+}
+
+/// Renders the feed `title` identified by `id` with the
+/// entries of the `incidents` and their servers, newest
+/// first. It was last updated `now` if there are none.
+pub fn render(title: &str, id: &str, incidents: &[(String, Incident)], now: u64) -> String {
+    let mut entries: Vec<Entry> = incidents.iter().flat_map(|(server, incident)| entries(server, incident)).collect();
+    entries.sort_by_key(|x| Reverse(x.updated));
+
+    let mut feed = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    feed.push_str(&format!("  <title>{}</title>\n", escape(title)));
+    feed.push_str(&format!("  <id>{}</id>\n", escape(id)));
+    feed.push_str(&format!("  <updated>{}</updated>\n", rfc3339(entries.first().map_or(now, |x| x.updated))));
+    feed.push_str("  <author><name>mcstatus-checker</name></author>\n");
+    for entry in entries {
+        feed.push_str("  <entry>\n");
+        feed.push_str(&format!("    <title>{}</title>\n", escape(&entry.title)));
+        feed.push_str(&format!("    <id>{}</id>\n", escape(&entry.id)));
+        feed.push_str(&format!("    <updated>{}</updated>\n", rfc3339(entry.updated)));
+        feed.push_str(&format!("    <summary>{}</summary>\n", escape(&entry.summary)));
+        feed.push_str("  </entry>\n");
+    }
+    feed.push_str("</feed>\n");
+    feed
+}
@@ -0,0 +1,51 @@
+//! Sparse fieldsets, keeping only the requested fields of a
+//! response.
+
+use rocket::serde::json::serde_json::{Map, Value};
+
+/// Keeps only the comma separated dotted paths of `fields` in
+/// `value`, i.e. `players.online,version.name`. Arrays on the
+/// way are narrowed element by element, paths that don't
+/// exist are left out.
+pub fn select(value: &Value, fields: &str) -> Value {
+    let mut selected = Value::Object(Map::new());
+    for field in fields.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+        let path: Vec<&str> = field.split('.').collect();
+        copy(value, &path, &mut selected);
+    }
+    selected
+}
+
+fn copy(source: &Value, path: &[&str], target: &mut Value) {
+    let (name, rest) = match path.split_first() {
+        Some(x) => x,
+        None => {
+            *target = source.clone();
+            return;
+        }
+    };
+
+    match source {
+        Value::Object(map) => {
+            if let Some(child) = map.get(*name) {
+                if !target.is_object() {
+                    *target = Value::Object(Map::new());
+                }
+                if let Value::Object(target) = target {
+                    copy(child, rest, target.entry(*name).or_insert(Value::Null));
+                }
+            }
+        }
+        Value::Array(items) => {
+            if target.as_array().is_none_or(|x| x.len() != items.len()) {
+                *target = Value::Array(vec![Value::Null; items.len()]);
+            }
+            if let Value::Array(target) = target {
+                for (item, target) in items.iter().zip(target.iter_mut()) {
+                    copy(item, path, target);
+                }
+            }
+        }
+        _ => {}
+    }
+}
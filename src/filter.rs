@@ -0,0 +1,212 @@
+//! Which servers may be checked, so a public instance can't
+//! be used to probe the network it runs in.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use mcstatus_checker::dns::Target;
+use crate::config::TargetConfig;
+
+/// A hostname, matching its subdomains as well, only its
+/// subdomains if given as `*.example.com`, or a range of IP
+/// addresses in CIDR notation.
+#[derive(Debug)]
+enum Rule {
+    Host { name: String, subdomains_only: bool },
+    Network(IpAddr, u8),
+}
+
+impl Rule {
+    fn parse(rule: &str) -> Result<Rule, String> {
+        let invalid = || format!("invalid target rule `{}`", rule);
+        let (addr, prefix) = match rule.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (rule, None),
+        };
+        let ip = match addr.parse::<IpAddr>() {
+            Ok(ip) => ip,
+            Err(_) if prefix.is_none() && !rule.is_empty() => {
+                let (name, subdomains_only) = match rule.strip_prefix("*.") {
+                    Some(name) => (name, true),
+                    None => (rule, false),
+                };
+                let name = name.trim_end_matches('.').to_ascii_lowercase();
+                if name.is_empty() {
+                    return Err(invalid());
+                }
+                return Ok(Rule::Host { name, subdomains_only });
+            }
+            Err(_) => return Err(invalid()),
+        };
+        let bits = if ip.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().ok().filter(|x| *x <= bits).ok_or_else(invalid)?,
+            None => bits,
+        };
+        Ok(Rule::Network(ip, prefix))
+    }
+
+    fn matches(&self, host: &str, ip: Option<IpAddr>) -> bool {
+        match self {
+            Rule::Host { name, subdomains_only } => {
+                let host = host.trim_end_matches('.').to_ascii_lowercase();
+                (!subdomains_only && host == *name) || host.strip_suffix(name.as_str()).is_some_and(|x| x.ends_with('.'))
+            }
+            Rule::Network(network, prefix) => {
+                ip.is_some_and(|ip| in_network(ip, *network, *prefix))
+            }
+        }
+    }
+}
+
+/// Decides whether a resolved target may be connected to.
+#[derive(Debug)]
+pub struct TargetFilter {
+    allow_private: bool,
+    allow: Vec<Rule>,
+    block: Vec<Rule>,
+}
+
+impl TargetFilter {
+    pub fn new(config: &TargetConfig) -> Result<TargetFilter, String> {
+        Ok(TargetFilter {
+            allow_private: config.allow_private,
+            allow: config.allow.iter().map(|x| Rule::parse(x)).collect::<Result<_, _>>()?,
+            block: config.block.iter().map(|x| Rule::parse(x)).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Whether `target`, resolved from the requested `host`,
+    /// may be checked. Blocked hosts and addresses are always
+    /// refused. If there are allowed ones, only those may be
+    /// checked, including private addresses. Otherwise
+    /// private addresses are refused unless allowed, together
+    /// with targets that couldn't be resolved, since it's
+    /// unknown where the connection would end up.
+    pub fn allows(&self, host: &str, target: &Target) -> bool {
+        let matches = |rule: &Rule| rule.matches(host, target.ip) || rule.matches(&target.host, target.ip);
+        if self.block.iter().any(matches) {
+            return false;
+        }
+        if !self.allow.is_empty() {
+            return self.allow.iter().any(matches);
+        }
+        if self.allow_private {
+            return true;
+        }
+        target.ip.is_some_and(|ip| !is_private(ip))
+    }
+}
+
+/// Whether the first `prefix` bits of `ip` and `network` are
+/// the same, comparing IPv4-mapped addresses as IPv4.
+fn in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    let ip = ip.to_canonical();
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Whether `ip` is a loopback, private, link-local or other
+/// address that isn't reachable on the internet, including
+/// IPv6 addresses that lead to such an IPv4 address.
+pub fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(ip) = embedded_v4(ip) {
+                return is_private_v4(ip);
+            }
+            ip.is_loopback() || ip.is_unspecified() || ip.is_multicast()
+                || ip.is_unique_local() || ip.is_unicast_link_local()
+        }
+    }
+}
+
+/// The IPv4 address embedded in an IPv4-mapped or -compatible
+/// address, a NAT64 one of `64:ff9b::/96` or a 6to4 one of
+/// `2002::/16`, which all connect to it.
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let bits = u128::from(ip);
+    match ip.segments() {
+        [0, 0, 0, 0, 0, 0 | 0xffff, ..] if !ip.is_loopback() && !ip.is_unspecified() => Some(Ipv4Addr::from(bits as u32)),
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(Ipv4Addr::from(bits as u32)),
+        [0x2002, ..] => Some(Ipv4Addr::from((bits >> 80) as u32)),
+        _ => None,
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+        || ip.is_broadcast() || ip.is_multicast()
+        // 0.0.0.0/8 "this network" and 100.64.0.0/10 carrier-grade NAT
+        || a == 0 || (a == 100 && (64..128).contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn matches_hosts_and_subdomains() {
+        let rule = Rule::parse("Example.com.").unwrap();
+        assert!(rule.matches("example.com", None));
+        assert!(rule.matches("mc.EXAMPLE.com.", None));
+        assert!(!rule.matches("badexample.com", None));
+    }
+
+    #[test]
+    fn matches_wildcards_to_subdomains_only() {
+        let rule = Rule::parse("*.example.com").unwrap();
+        assert!(rule.matches("mc.example.com", None));
+        assert!(rule.matches("a.b.example.com", None));
+        assert!(!rule.matches("example.com", None));
+    }
+
+    #[test]
+    fn matches_networks() {
+        let rule = Rule::parse("203.0.113.0/24").unwrap();
+        assert!(rule.matches("mc.example.com", Some(ip("203.0.113.7"))));
+        assert!(rule.matches("mc.example.com", Some(ip("::ffff:203.0.113.7"))));
+        assert!(!rule.matches("mc.example.com", Some(ip("203.0.114.7"))));
+        assert!(!rule.matches("mc.example.com", None));
+
+        let rule = Rule::parse("2001:db8::/32").unwrap();
+        assert!(rule.matches("mc.example.com", Some(ip("2001:db8::1"))));
+        assert!(!rule.matches("mc.example.com", Some(ip("2001:db9::1"))));
+
+        let rule = Rule::parse("198.51.100.1").unwrap();
+        assert!(rule.matches("mc.example.com", Some(ip("198.51.100.1"))));
+        assert!(!rule.matches("mc.example.com", Some(ip("198.51.100.2"))));
+
+        assert!(Rule::parse("0.0.0.0/0").unwrap().matches("mc.example.com", Some(ip("8.8.8.8"))));
+    }
+
+    #[test]
+    fn refuses_invalid_rules() {
+        for rule in ["", "*.", "10.0.0.0/33", "::/129", "10.0.0.0/x", "example.com/8"] {
+            assert!(Rule::parse(rule).is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn finds_private_addresses() {
+        for private in ["10.0.0.1", "127.0.0.1", "192.168.1.1", "169.254.0.1", "100.64.0.1", "0.1.2.3", "::1", "::", "fe80::1", "fc00::1", "::ffff:10.0.0.1", "::10.0.0.1", "64:ff9b::10.0.0.1", "2002:a00:1::"] {
+            assert!(is_private(ip(private)), "{}", private);
+        }
+        for public in ["8.8.8.8", "203.0.113.7", "2001:4860:4860::8888", "::ffff:8.8.8.8", "64:ff9b::8.8.8.8", "2002:808:808::"] {
+            assert!(!is_private(ip(public)), "{}", public);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! The mod list Forge adds to the status response, as
+//! `modinfo` up to 1.12 and as `forgeData` since 1.13.
+
+use serde::{Deserialize, Serialize};
+
+/// The `modinfo` section of 1.7 to 1.12.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegacyModInfo {
+    #[serde(rename = "type", default)]
+    pub kind: String,
+
+    #[serde(rename = "modList", default)]
+    pub mod_list: Vec<LegacyMod>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegacyMod {
+    pub modid: String,
+    pub version: String,
+}
+
+/// The `forgeData` section since 1.13.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForgeData {
+    #[serde(default)]
+    pub channels: Vec<ForgeChannel>,
+
+    #[serde(default)]
+    pub mods: Vec<ForgeMod>,
+
+    #[serde(rename = "fmlNetworkVersion")]
+    pub fml_network_version: i32,
+
+    #[serde(default)]
+    pub truncated: bool,
+
+    /// Since 1.18 the mods and channels are encoded into this
+    /// string instead.
+    #[serde(default)]
+    pub d: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForgeChannel {
+    pub res: String,
+    pub version: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForgeMod {
+    #[serde(rename = "modId")]
+    pub mod_id: String,
+    pub modmarker: String,
+}
+
+/// A loaded mod.
+#[derive(Debug, Clone, Serialize)]
+pub struct Mod {
+    /// The mod ID, i.e. "forge".
+    pub id: String,
+
+    /// The mod version, missing for mods Forge marked as
+    /// only required on the server.
+    pub version: Option<String>,
+}
+
+/// A network channel registered by a mod.
+#[derive(Debug, Clone, Serialize)]
+pub struct Channel {
+    /// The channel name, i.e. "forge:tier_sorting".
+    pub name: String,
+
+    /// The channel's protocol version.
+    pub version: String,
+
+    /// Whether the channel has to be present on the client.
+    pub required: bool,
+}
+
+/// The normalized mod list of a modded server.
+#[derive(Debug, Clone, Serialize)]
+pub struct ForgeInfo {
+    /// The FML network protocol version, 1 for the `modinfo`
+    /// of 1.7 to 1.12.
+    pub fml_network_version: i32,
+
+    /// The loaded mods.
+    pub mods: Vec<Mod>,
+
+    /// The registered network channels, unknown before 1.13.
+    pub channels: Vec<Channel>,
+
+    /// Whether the server left out mods to keep the response
+    /// small.
+    pub truncated: bool,
+}
+
+impl ForgeInfo {
+    /// Normalizes whichever section the server sent.
+    pub fn from_status(forge_data: Option<&ForgeData>, modinfo: Option<&LegacyModInfo>) -> Option<ForgeInfo> {
+        if let Some(data) = forge_data {
+            if let Some(info) = data.d.as_deref().and_then(decode_optimized) {
+                return Some(ForgeInfo { fml_network_version: data.fml_network_version, ..info });
+            }
+            return Some(ForgeInfo {
+                fml_network_version: data.fml_network_version,
+                mods: data.mods.iter()
+                    .map(|x| Mod { id: x.mod_id.clone(), version: Some(x.modmarker.clone()).filter(|x| x != IGNORE_SERVER_ONLY) })
+                    .collect(),
+                channels: data.channels.iter()
+                    .map(|x| Channel { name: x.res.clone(), version: x.version.clone(), required: x.required })
+                    .collect(),
+                truncated: data.truncated,
+            });
+        }
+
+        modinfo.filter(|x| x.kind == "FML").map(|info| ForgeInfo {
+            fml_network_version: 1,
+            mods: info.mod_list.iter()
+                .map(|x| Mod { id: x.modid.clone(), version: Some(x.version.clone()) })
+                .collect(),
+            channels: Vec::new(),
+            truncated: false,
+        })
+    }
+}
+
+/// The version Forge reports for mods it marked as only
+/// required on the server, implied in the `d` string.
+const IGNORE_SERVER_ONLY: &str = "OHNOES\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}\u{1f631}";
+
+/// Decodes the `d` string of 1.18+: every UTF-16 code unit
+/// carries 15 bits, the first two hold the byte length. The
+/// bytes are the binary mod and channel list.
+fn decode_optimized(d: &str) -> Option<ForgeInfo> {
+    let units: Vec<u16> = d.encode_utf16().collect();
+    if units.len() < 2 {
+        return None;
+    }
+    let size = (units[0] as usize) | ((units[1] as usize) << 15);
+    if size > units.len() * 2 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(size);
+    let mut buffer = 0u32;
+    let mut bits = 0u32;
+    for unit in &units[2..] {
+        while bits >= 8 {
+            bytes.push(buffer as u8);
+            buffer >>= 8;
+            bits -= 8;
+        }
+        buffer |= ((unit & 0x7FFF) as u32) << bits;
+        bits += 15;
+    }
+    // The leftover bits
+    while bytes.len() < size {
+        bytes.push(buffer as u8);
+        buffer >>= 8;
+    }
+
+    let mut reader = Reader(&bytes);
+    let truncated = reader.bool()?;
+    let mod_count = reader.u16()?;
+    let mut mods = Vec::with_capacity(mod_count as usize);
+    let mut channels = Vec::new();
+    for _ in 0..mod_count {
+        let flags = reader.varint()?;
+        let channel_count = flags >> 1;
+        let id = reader.string()?;
+        let version = if flags & 1 != 0 { None } else { Some(reader.string()?) };
+        for _ in 0..channel_count {
+            let name = reader.string()?;
+            let version = reader.string()?;
+            let required = reader.bool()?;
+            channels.push(Channel { name: format!("{}:{}", id, name), version, required });
+        }
+        mods.push(Mod { id, version });
+    }
+    let channel_count = reader.varint()?;
+    for _ in 0..channel_count {
+        let name = reader.string()?;
+        let version = reader.string()?;
+        let required = reader.bool()?;
+        channels.push(Channel { name, version, required });
+    }
+
+    Some(ForgeInfo { fml_network_version: 0, mods, channels, truncated })
+}
+
+/// Reads the primitives of Minecraft's packet format.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> Option<&[u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        self.bytes(1).map(|x| x[0] != 0)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|x| u16::from_be_bytes([x[0], x[1]]))
+    }
+
+    fn varint(&mut self) -> Option<u32> {
+        let mut value = 0u32;
+        for i in 0..5 {
+            let byte = self.bytes(1)?[0];
+            value |= ((byte & 0x7F) as u32) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.varint()? as usize;
+        self.bytes(len).map(|x| String::from_utf8_lossy(x).into_owned())
+    }
+}
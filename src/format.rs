@@ -0,0 +1,177 @@
+//! Serializes responses as JSON, XML, MessagePack or YAML,
+//! chosen with `?format=` or the `Accept` header, and errors
+//! as problems of RFC 7807 if asked for.
+
+use rocket::http::{ContentType, Header, MediaType, Status};
+use rocket::response::{self, Responder};
+use rocket::serde::json::{serde_json, Value};
+use rocket::Request;
+use serde::{Serialize, Serializer};
+use serde::ser::Error;
+use crate::problem::Problem;
+use crate::is_v2;
+
+/// The formats a response can be serialized in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Xml,
+    MsgPack,
+    Yaml,
+
+    /// JSON, with errors as `application/problem+json`.
+    Problem,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "xml" => Some(Format::Xml),
+            "msgpack" | "messagepack" => Some(Format::MsgPack),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "problem" => Some(Format::Problem),
+            _ => None,
+        }
+    }
+
+    fn from_media_type(media_type: &MediaType) -> Option<Format> {
+        match (media_type.top().as_str(), media_type.sub().as_str()) {
+            ("application", "json") => Some(Format::Json),
+            ("application", "problem+json") => Some(Format::Problem),
+            ("application" | "text", "xml") => Some(Format::Xml),
+            ("application", "msgpack" | "x-msgpack" | "vnd.msgpack") => Some(Format::MsgPack),
+            ("application" | "text", "yaml" | "x-yaml") => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+
+    /// The format asked for with `?format=`, otherwise the
+    /// most preferred one of the `Accept` header that is
+    /// supported, JSON by default. Unknown names of
+    /// `?format=` are refused.
+    fn of(request: &Request<'_>) -> Result<Format, Status> {
+        if let Some(name) = request.query_value::<&str>("format") {
+            let name = name.map_err(|_| Status::BadRequest)?;
+            return Format::from_name(name).ok_or(Status::BadRequest);
+        }
+
+        let format = request.accept().and_then(|accept| {
+            let mut media_types: Vec<_> = accept.iter().collect();
+            media_types.sort_by(|a, b| b.weight_or(1.0).total_cmp(&a.weight_or(1.0)));
+            media_types.into_iter().find_map(|x| Format::from_media_type(x.media_type()))
+        });
+        Ok(format.unwrap_or(Format::Json))
+    }
+
+    fn content_type(self) -> ContentType {
+        match self {
+            Format::Json | Format::Problem => ContentType::JSON,
+            Format::Xml => ContentType::XML,
+            Format::MsgPack => ContentType::new("application", "msgpack"),
+            Format::Yaml => ContentType::new("application", "yaml"),
+        }
+    }
+
+    fn serialize<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Json | Format::Problem => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Format::Xml => {
+                // Through a JSON value, since the XML serializer
+                // doesn't support flattened and untagged fields
+                let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+                quick_xml::se::to_string_with_root("response", &value).map(String::into_bytes).map_err(|e| e.to_string())
+            }
+            Format::MsgPack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::to_string(value).map(String::into_bytes).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Serializes a value as version 1 of the API responds it,
+/// with errors as the name of their code instead of objects.
+pub struct V1<'a, T>(pub &'a T);
+
+/// The code of a serialized error, if `map` is one.
+fn error_code(map: &serde_json::Map<String, Value>) -> Option<Value> {
+    let is_error = map.get("message").is_some_and(Value::is_string)
+        && map.keys().all(|x| matches!(x.as_str(), "code" | "message" | "cause"));
+    map.get("code").filter(|x| is_error && x.is_string()).cloned()
+}
+
+/// Replaces the serialized errors in `value` with their code,
+/// telling whether there were any.
+fn replace_errors(value: &mut Value) -> bool {
+    if let Some(code) = value.as_object().and_then(error_code) {
+        *value = code;
+        return true;
+    }
+    match value {
+        Value::Object(map) => map.values_mut().fold(false, |found, x| replace_errors(x) | found),
+        Value::Array(items) => items.iter_mut().fold(false, |found, x| replace_errors(x) | found),
+        _ => false,
+    }
+}
+
+impl<T: Serialize> Serialize for V1<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut value = serde_json::to_value(self.0).map_err(S::Error::custom)?;
+        // Values without errors keep the order of their fields
+        if !replace_errors(&mut value) {
+            return self.0.serialize(serializer);
+        }
+        value.serialize(serializer)
+    }
+}
+
+/// Responds a value in the format the client asked for.
+#[derive(Debug)]
+pub struct Formatted<T>(pub T);
+
+impl<'r, 'o: 'r, T: Serialize> Responder<'r, 'o> for Formatted<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        respond(Format::of(request)?, &self.0, request)
+    }
+}
+
+/// Responds a value like [`Formatted`], but as JSON if the
+/// format asked for is unknown, for errors that have to be
+/// responded anyway.
+#[derive(Debug)]
+pub struct FormattedOrJson<T>(pub T);
+
+impl<'r, 'o: 'r, T: Serialize> Responder<'r, 'o> for FormattedOrJson<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        respond(Format::of(request).unwrap_or(Format::Json), &self.0, request)
+    }
+}
+
+/// Serializes errors as objects for `/v2` and problems, which
+/// need their cause, and as codes for version 1. Tells
+/// caches that the response depends on `Accept` unless
+/// `?format=` chose the format.
+fn respond<'r, 'o: 'r, T: Serialize>(format: Format, value: &T, request: &'r Request<'_>) -> response::Result<'o> {
+    let mut response = serialize(format, value, request)?;
+    if request.query_value::<&str>("format").is_none() {
+        response.adjoin_header(Header::new("Vary", "Accept"));
+    }
+    Ok(response)
+}
+
+fn serialize<'r, 'o: 'r, T: Serialize>(format: Format, value: &T, request: &'r Request<'_>) -> response::Result<'o> {
+    if format == Format::Problem {
+        let value = serde_json::to_value(value).map_err(|_| Status::InternalServerError)?;
+        if let Some(problem) = Problem::of(&value, request) {
+            return problem.respond_to(request);
+        }
+    }
+    let body = match is_v2(request.uri()) {
+        true => format.serialize(value),
+        false => format.serialize(&V1(value)),
+    };
+    let body = body.map_err(|e| {
+        error!("failed to serialize the response as {:?}: {}", format, e);
+        Status::InternalServerError
+    })?;
+    (format.content_type(), body).respond_to(request)
+}
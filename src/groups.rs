@@ -0,0 +1,95 @@
+//! Summaries of the monitored servers sharing a tag, like the
+//! backends of a proxy network.
+
+use serde::Serialize;
+use crate::history::Record;
+
+/// Whether the servers of a group are online.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupState {
+    /// All of them.
+    Up,
+
+    /// Some of them.
+    Partial,
+
+    /// None of them.
+    Down,
+}
+
+/// The servers of a group as of their latest checks.
+#[derive(Debug, Serialize)]
+pub struct GroupStatus {
+    pub tag: String,
+    pub state: GroupState,
+
+    /// The number of servers online.
+    pub online: usize,
+
+    /// The number of servers in the group.
+    pub total: usize,
+
+    /// The players online on all servers.
+    pub players_online: u64,
+
+    /// The sum of the maximum numbers of players.
+    pub players_max: u64,
+
+    pub servers: Vec<GroupServer>,
+}
+
+/// A server of a group as of its latest check.
+#[derive(Debug, Serialize)]
+pub struct GroupServer {
+    pub address: String,
+
+    /// Whether the server was online, absent if it hasn't been
+    /// checked yet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub online: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub players_online: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub players_max: Option<u32>,
+
+    /// When the server was checked, in seconds since the Unix
+    /// epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<u64>,
+}
+
+impl GroupServer {
+    pub fn new(address: String, latest: Option<&Record>) -> GroupServer {
+        let online = latest.filter(|x| x.online);
+        GroupServer {
+            address,
+            online: latest.map(|x| x.online),
+            players_online: online.and_then(|x| x.players_online).map(|x| x.max(0) as u32),
+            players_max: online.and_then(|x| x.players_max).map(|x| x.max(0) as u32),
+            checked_at: latest.map(|x| x.checked_at.max(0) as u64),
+        }
+    }
+}
+
+/// Sums up the `servers` with `tag`. Servers that haven't
+/// been checked yet count as offline.
+pub fn summarize(tag: &str, servers: Vec<GroupServer>) -> GroupStatus {
+    let online = servers.iter().filter(|x| x.online == Some(true)).count();
+    let state = match online {
+        0 => GroupState::Down,
+        x if x == servers.len() => GroupState::Up,
+        _ => GroupState::Partial,
+    };
+    GroupStatus {
+        tag: tag.to_string(),
+        state,
+        online,
+        total: servers.len(),
+        players_online: servers.iter().filter_map(|x| x.players_online).map(u64::from).sum(),
+        players_max: servers.iter().filter_map(|x| x.players_max).map(u64::from).sum(),
+        servers,
+    }
+}
@@ -0,0 +1,107 @@
+use rocket::http::{ContentType, Header};
+use rocket::response::{self, Responder};
+use rocket::Request;
+use rocket::serde::json::serde_json;
+use mcstatus_checker::cache::Checked;
+use mcstatus_checker::java::{Ping, StatusResponse};
+use crate::changes;
+
+/// Wraps a responder to add headers to its response.
+pub struct WithHeaders<R> {
+    inner: R,
+    headers: Vec<Header<'static>>,
+}
+
+impl<R> WithHeaders<R> {
+    pub fn new(inner: R) -> Self {
+        WithHeaders { inner, headers: Vec::new() }
+    }
+
+    /// Adds a header, replacing any header of the same name
+    /// set by the inner responder.
+    pub fn header(mut self, header: Header<'static>) -> Self {
+        self.headers.push(header);
+        self
+    }
+
+    /// Adds all `headers` like `header` does.
+    pub fn headers(self, headers: Vec<Header<'static>>) -> Self {
+        headers.into_iter().fold(self, |x, header| x.header(header))
+    }
+
+    /// Adds the `ping_headers` of `ping`.
+    pub fn ping(self, ping: Option<&Ping>) -> Self {
+        self.headers(ping_headers(ping))
+    }
+
+    /// Adds the `checked_headers` of `checked`.
+    pub fn checked(self, checked: Option<&Checked>) -> Self {
+        self.headers(checked_headers(checked))
+    }
+}
+
+/// `X-MC-Online`, and for servers that answered
+/// `X-MC-Players-Online`, `X-MC-Players-Max` and
+/// `X-MC-Latency` in milliseconds if it was measured, so
+/// monitors don't have to parse the body.
+pub fn ping_headers(ping: Option<&Ping>) -> Vec<Header<'static>> {
+    let mut headers = vec![Header::new("X-MC-Online", ping.is_some().to_string())];
+    if let Some(ping) = ping {
+        headers.push(Header::new("X-MC-Players-Online", ping.status.players.online.to_string()));
+        headers.push(Header::new("X-MC-Players-Max", ping.status.players.max.to_string()));
+        if let Some(latency) = ping.latency {
+            headers.push(Header::new("X-MC-Latency", latency.as_millis().to_string()));
+        }
+    }
+    headers
+}
+
+/// The `ping_headers` of a checked status, `X-Cache` as
+/// `hit` if it came from the cache, `stale` if it was served
+/// as the server couldn't be pinged, or `miss`, and the
+/// `ETag` of the status.
+pub fn checked_headers(checked: Option<&Checked>) -> Vec<Header<'static>> {
+    let mut headers = ping_headers(checked.map(|x| &x.ping));
+    if let Some(checked) = checked {
+        headers.push(Header::new("X-Cache", cache_status(checked)));
+        headers.push(Header::new("ETag", etag(&checked.ping.status)));
+    }
+    headers
+}
+
+/// A weak tag of the status the server responded, which
+/// stays the same while only the latency or cache age
+/// changes.
+fn etag(status: &StatusResponse) -> String {
+    format!("W/\"{}\"", changes::hash(&serde_json::to_string(status).unwrap_or_default()))
+}
+
+/// The tag of the status along with everything else the
+/// body depends on: the format, and the version and
+/// parameters of the route, like the selected fields or the
+/// label of a badge.
+fn response_etag(etag: &str, content_type: &ContentType, request: &Request<'_>) -> String {
+    format!("W/\"{}\"", changes::hash(&format!("{} {} {}", etag, content_type, request.uri())))
+}
+
+fn cache_status(checked: &Checked) -> &'static str {
+    match (checked.stale, checked.age) {
+        (true, _) => "stale",
+        (false, Some(_)) => "hit",
+        (false, None) => "miss",
+    }
+}
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for WithHeaders<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        let mut response = self.inner.respond_to(request)?;
+        for header in self.headers {
+            let header = match (header.name().as_str(), response.content_type()) {
+                ("ETag", Some(content_type)) => Header::new("ETag", response_etag(header.value(), &content_type, request)),
+                _ => header,
+            };
+            response.set_header(header);
+        }
+        Ok(response)
+    }
+}
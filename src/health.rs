@@ -0,0 +1,62 @@
+//! Whether the checker itself is ready to serve, for the
+//! probes of Kubernetes and load balancers.
+
+use std::time::Duration;
+use hickory_resolver::TokioAsyncResolver;
+use serde::Serialize;
+use mcstatus_checker::dns;
+use crate::history::History;
+use crate::monitor::Heartbeat;
+
+/// How long the scheduler may not look for due servers before
+/// it counts as stuck.
+const SCHEDULER_PERIOD: Duration = Duration::from_secs(30);
+
+/// How long the history may take to answer.
+const STORAGE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The state of a dependency of the checker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckState {
+    Ok,
+    Failing,
+
+    /// Not used, like the scheduler without monitored servers.
+    Disabled,
+}
+
+impl CheckState {
+    fn of(ok: bool) -> CheckState {
+        if ok { CheckState::Ok } else { CheckState::Failing }
+    }
+}
+
+/// The states of the dependencies, ready if none is failing.
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub scheduler: CheckState,
+    pub storage: CheckState,
+    pub dns: CheckState,
+}
+
+/// Checks the scheduler and history if monitoring is enabled,
+/// and whether `dns_name` resolves.
+pub async fn readiness(history: Option<&History>, heartbeat: Option<&Heartbeat>, resolver: &TokioAsyncResolver, dns_name: &str) -> Readiness {
+    let (scheduler, storage) = match history {
+        Some(history) => {
+            let reachable = tokio::time::timeout(STORAGE_TIMEOUT, history.ping()).await.is_ok_and(|x| x.is_ok());
+            let running = heartbeat.is_some_and(|x| x.alive(SCHEDULER_PERIOD));
+            (CheckState::of(running), CheckState::of(reachable))
+        }
+        None => (CheckState::Disabled, CheckState::Disabled),
+    };
+    let dns = CheckState::of(dns::healthy(resolver, dns_name).await);
+    Readiness {
+        ready: [scheduler, storage, dns].iter().all(|x| *x != CheckState::Failing),
+        scheduler,
+        storage,
+        dns,
+    }
+}
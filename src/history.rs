@@ -0,0 +1,663 @@
+//! The results of scheduled checks, stored in an embedded
+//! SQLite database so they survive restarts.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures::stream::{Stream, StreamExt};
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use mcstatus_checker::java::{Ping, ServerPlayer, ServerPlayers};
+use crate::changes::Fingerprint;
+use crate::config::HistoryConfig;
+
+/// The check results of all monitored servers. Clones share
+/// their connections.
+#[derive(Clone)]
+pub struct History {
+    pool: SqlitePool,
+}
+
+/// One stored check, reduced to whether the server was
+/// online.
+#[derive(Debug, Clone, Copy)]
+pub struct Check {
+    /// When the server was checked, in seconds since the Unix
+    /// epoch.
+    pub checked_at: u64,
+
+    pub online: bool,
+
+    /// Whether the server was under maintenance.
+    pub maintenance: bool,
+}
+
+/// The players of a server over one step of a time series.
+#[derive(Debug, Serialize)]
+pub struct PlayersPoint {
+    /// The start of the step, in seconds since the Unix epoch.
+    pub timestamp: u64,
+
+    /// The average number of players online at the checks of
+    /// the step.
+    pub online: f64,
+
+    /// The highest maximum number of players of the step.
+    pub max: u32,
+}
+
+/// The most players a server had online at once.
+#[derive(Debug, Serialize)]
+pub struct Peak {
+    pub players: u32,
+
+    /// When the players were first online, in seconds since
+    /// the Unix epoch.
+    pub reached_at: u64,
+}
+
+/// The peaks of a server, absent while it wasn't online.
+#[derive(Debug, Serialize)]
+pub struct Peaks {
+    /// The peak of the current day in UTC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub today: Option<Peak>,
+
+    /// The peak of the last 7 days, today included.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_7d: Option<Peak>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub all_time: Option<Peak>,
+}
+
+/// A time a player was seen in the sample of a server.
+#[derive(Debug, Serialize)]
+pub struct Session {
+    /// The name of the player.
+    pub player: String,
+
+    /// The UUID of the player.
+    pub id: String,
+
+    /// The first check the player was seen at, in seconds
+    /// since the Unix epoch.
+    pub start: u64,
+
+    /// The last check the player was seen at, unless they are
+    /// still online.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<u64>,
+
+    /// How long the player was online in seconds, until now if
+    /// they still are.
+    pub duration: u64,
+
+    /// Whether the sample listed fewer players than were
+    /// online at any check of the session, so the player may
+    /// have been online without being seen.
+    pub truncated: bool,
+}
+
+/// One stored check with everything known about it.
+#[derive(Debug)]
+pub struct Record {
+    /// When the server was checked, in seconds since the Unix
+    /// epoch.
+    pub checked_at: i64,
+    pub online: bool,
+    pub latency_ms: Option<i64>,
+    pub players_online: Option<i64>,
+    pub players_max: Option<i64>,
+    pub version: Option<String>,
+    pub protocol: Option<i64>,
+    pub maintenance: bool,
+}
+
+/// The columns of a record as they are selected.
+type RecordRow = (i64, bool, Option<i64>, Option<i64>, Option<i64>, Option<String>, Option<i64>, bool);
+
+/// The header of the CSV lines of records.
+pub const CSV_HEADER: &str = "checked_at,online,latency_ms,players_online,players_max,version,protocol,maintenance\n";
+
+impl Record {
+    /// The record as a line of CSV, in the order of
+    /// [`CSV_HEADER`].
+    pub fn to_csv(&self) -> String {
+        fn field<T: ToString>(value: &Option<T>) -> String {
+            value.as_ref().map_or(String::new(), |x| x.to_string())
+        }
+
+        let version = match &self.version {
+            Some(x) if x.contains([',', '"', '\n', '\r']) => format!("\"{}\"", x.replace('"', "\"\"")),
+            Some(x) => x.clone(),
+            None => String::new(),
+        };
+        format!(
+            "{},{},{},{},{},{},{},{}\n",
+            self.checked_at, self.online, field(&self.latency_ms), field(&self.players_online),
+            field(&self.players_max), version, field(&self.protocol), self.maintenance,
+        )
+    }
+}
+
+/// A time a monitored server was offline.
+#[derive(Debug, Serialize)]
+pub struct Incident {
+    pub id: i64,
+
+    /// The first failed check, in seconds since the Unix
+    /// epoch.
+    pub start: u64,
+
+    /// The first successful check afterwards, if the server is
+    /// online again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<u64>,
+
+    /// How long the server was offline in seconds, until now
+    /// if it still is.
+    pub duration: u64,
+
+    /// Why the server couldn't be pinged, like
+    /// `ConnectTimeout`, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The columns of an incident along with its server, as
+/// they are selected.
+type ServerIncidentRow = (String, i64, i64, Option<i64>, Option<String>);
+
+impl Incident {
+    /// The incident of a row of `id, started_at, ended_at,
+    /// error`, lasting until `now` if it hasn't ended.
+    fn from_row((id, start, end, error): (i64, i64, Option<i64>, Option<String>), now: i64) -> Incident {
+        Incident {
+            id,
+            start: start.max(0) as u64,
+            end: end.map(|x| x.max(0) as u64),
+            duration: (end.unwrap_or(now) - start).max(0) as u64,
+            error,
+        }
+    }
+}
+
+/// Seconds since the Unix epoch, as stored in the database.
+fn timestamp(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs() as i64)
+}
+
+/// The seconds of `days` days.
+fn days(days: u64) -> i64 {
+    (days * 24 * 60 * 60) as i64
+}
+
+impl History {
+    /// Opens the database at `path`, creating it and its
+    /// table if they don't exist yet.
+    pub async fn open(path: &str) -> Result<History, sqlx::Error> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS checks (
+                server TEXT NOT NULL,
+                checked_at INTEGER NOT NULL,
+                online INTEGER NOT NULL,
+                latency_ms INTEGER,
+                players_online INTEGER,
+                players_max INTEGER,
+                version TEXT,
+                protocol INTEGER,
+                downsampled INTEGER NOT NULL DEFAULT 0,
+                maintenance INTEGER NOT NULL DEFAULT 0
+            )",
+        ).execute(&pool).await?;
+        // Databases from before these columns lack them
+        for column in ["downsampled", "maintenance"] {
+            let (exists,): (bool,) = sqlx::query_as("SELECT COUNT(*) > 0 FROM pragma_table_info('checks') WHERE name = ?")
+                .bind(column)
+                .fetch_one(&pool).await?;
+            if !exists {
+                sqlx::query(&format!("ALTER TABLE checks ADD COLUMN {} INTEGER NOT NULL DEFAULT 0", column))
+                    .execute(&pool).await?;
+            }
+        }
+        sqlx::query("CREATE INDEX IF NOT EXISTS checks_server_checked_at ON checks (server, checked_at)")
+            .execute(&pool).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS incidents (
+                id INTEGER PRIMARY KEY,
+                server TEXT NOT NULL,
+                started_at INTEGER NOT NULL,
+                ended_at INTEGER,
+                error TEXT
+            )",
+        ).execute(&pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS incidents_server_started_at ON incidents (server, started_at)")
+            .execute(&pool).await?;
+        // Peaks are kept per day, since downsampling loses
+        // them. New tables start with the peaks of the checks.
+        let (peaks_exist,): (bool,) = sqlx::query_as("SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'peaks'")
+            .fetch_one(&pool).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS peaks (
+                server TEXT NOT NULL,
+                day INTEGER NOT NULL,
+                players INTEGER NOT NULL,
+                reached_at INTEGER NOT NULL,
+                PRIMARY KEY (server, day)
+            )",
+        ).execute(&pool).await?;
+        if !peaks_exist {
+            // SQLite takes `checked_at` from the row with the
+            // most players
+            sqlx::query(
+                "INSERT INTO peaks (server, day, players, reached_at)
+                 SELECT server, checked_at / 86400, MAX(players_online), checked_at FROM checks
+                 WHERE online AND NOT downsampled GROUP BY server, checked_at / 86400",
+            ).execute(&pool).await?;
+        }
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id INTEGER PRIMARY KEY,
+                server TEXT NOT NULL,
+                player_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                started_at INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL,
+                ended_at INTEGER,
+                truncated INTEGER NOT NULL DEFAULT 0
+            )",
+        ).execute(&pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS sessions_server_started_at ON sessions (server, started_at)")
+            .execute(&pool).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS fingerprints (
+                server TEXT PRIMARY KEY,
+                motd TEXT NOT NULL,
+                version TEXT NOT NULL,
+                icon TEXT
+            )",
+        ).execute(&pool).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS servers (
+                address TEXT PRIMARY KEY,
+                config TEXT NOT NULL
+            )",
+        ).execute(&pool).await?;
+
+        Ok(History { pool })
+    }
+
+    /// Stores the result of checking `server` at `checked_at`,
+    /// with the status if it was online, and whether it was
+    /// under maintenance.
+    pub async fn record(&self, server: &str, checked_at: SystemTime, ping: Option<&Ping>, maintenance: bool) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO checks (server, checked_at, online, latency_ms, players_online, players_max, version, protocol, maintenance)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+            .bind(server)
+            .bind(timestamp(checked_at))
+            .bind(ping.is_some())
+            .bind(ping.and_then(|x| x.latency).map(|x| x.as_millis() as i64))
+            .bind(ping.map(|x| x.status.players.online as i64))
+            .bind(ping.map(|x| x.status.players.max as i64))
+            .bind(ping.map(|x| x.status.version.name.clone()))
+            .bind(ping.map(|x| x.status.version.protocol))
+            .bind(maintenance)
+            .execute(&self.pool).await?;
+
+        if let Some(ping) = ping {
+            let checked_at = timestamp(checked_at);
+            sqlx::query(
+                "INSERT INTO peaks (server, day, players, reached_at) VALUES (?1, ?2 / 86400, ?3, ?2)
+                 ON CONFLICT (server, day) DO UPDATE SET players = excluded.players, reached_at = excluded.reached_at
+                 WHERE excluded.players > peaks.players",
+            )
+                .bind(server)
+                .bind(checked_at)
+                .bind(ping.status.players.online as i64)
+                .execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    /// Updates the sessions of the players of `server` with the
+    /// sample of a check at `checked_at`. Players missing from
+    /// a complete sample are offline, but a truncated one only
+    /// ends their session after `timeout`.
+    pub async fn track_sessions(&self, server: &str, checked_at: SystemTime, players: &ServerPlayers, timeout: Duration) -> Result<(), sqlx::Error> {
+        let now = timestamp(checked_at);
+        // Servers hiding their players fill the sample with
+        // lines of text under the nil UUID
+        let sample: Vec<&ServerPlayer> = players.sample.iter().flatten()
+            .filter(|x| !x.id.trim_start_matches(['0', '-']).is_empty())
+            .collect();
+        let truncated = sample.len() < players.online as usize;
+
+        let mut transaction = self.pool.begin().await?;
+        let open: Vec<(i64, String, i64)> = sqlx::query_as("SELECT id, player_id, last_seen FROM sessions WHERE server = ? AND ended_at IS NULL")
+            .bind(server)
+            .fetch_all(&mut *transaction).await?;
+        let mut continued = Vec::new();
+        for (id, player_id, last_seen) in &open {
+            // Sessions not seen for longer, like while the server
+            // was offline, are over even if the player is back
+            let expired = now - last_seen > timeout.as_secs() as i64;
+            let seen = sample.iter().any(|x| x.id == *player_id);
+            if seen && !expired {
+                sqlx::query("UPDATE sessions SET last_seen = ?, truncated = truncated OR ? WHERE id = ?")
+                    .bind(now)
+                    .bind(truncated)
+                    .bind(id)
+                    .execute(&mut *transaction).await?;
+                continued.push(player_id.as_str());
+            } else if !seen && truncated && !expired {
+                sqlx::query("UPDATE sessions SET truncated = 1 WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *transaction).await?;
+            } else {
+                sqlx::query("UPDATE sessions SET ended_at = last_seen WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *transaction).await?;
+            }
+        }
+        for player in sample.iter().filter(|x| !continued.contains(&x.id.as_str())) {
+            sqlx::query("INSERT INTO sessions (server, player_id, name, started_at, last_seen, truncated) VALUES (?, ?, ?, ?, ?, ?)")
+                .bind(server)
+                .bind(&player.id)
+                .bind(&player.name)
+                .bind(now)
+                .bind(now)
+                .bind(truncated)
+                .execute(&mut *transaction).await?;
+        }
+        transaction.commit().await
+    }
+
+    /// The sessions of the players of `server`, or only of the
+    /// `player` with that name or UUID, newest first, and the
+    /// number of them. Sessions not seen for `timeout` as of
+    /// `now` ended when they were last seen.
+    pub async fn sessions(&self, server: &str, player: Option<&str>, offset: u64, limit: u64, now: SystemTime, timeout: Duration) -> Result<(Vec<Session>, u64), sqlx::Error> {
+        let now = timestamp(now);
+        let (total,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sessions WHERE server = ?1 AND (?2 IS NULL OR name = ?2 COLLATE NOCASE OR player_id = ?2)")
+            .bind(server)
+            .bind(player)
+            .fetch_one(&self.pool).await?;
+        let rows: Vec<(String, String, i64, i64, Option<i64>, bool)> = sqlx::query_as(
+            "SELECT name, player_id, started_at, last_seen, ended_at, truncated FROM sessions
+             WHERE server = ?1 AND (?2 IS NULL OR name = ?2 COLLATE NOCASE OR player_id = ?2)
+             ORDER BY started_at DESC LIMIT ?3 OFFSET ?4",
+        )
+            .bind(server)
+            .bind(player)
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .fetch_all(&self.pool).await?;
+        let sessions = rows.into_iter()
+            .map(|(player, id, start, last_seen, ended_at, truncated)| {
+                let end = ended_at.or((now - last_seen > timeout.as_secs() as i64).then_some(last_seen));
+                Session {
+                    player,
+                    id,
+                    start: start.max(0) as u64,
+                    end: end.map(|x| x.max(0) as u64),
+                    duration: (end.unwrap_or(now) - start).max(0) as u64,
+                    truncated,
+                }
+            })
+            .collect();
+        Ok((sessions, total.max(0) as u64))
+    }
+
+    /// The watched properties of `server` at its last
+    /// successful check, if there was one.
+    pub async fn fingerprint(&self, server: &str) -> Result<Option<Fingerprint>, sqlx::Error> {
+        let row: Option<(String, String, Option<String>)> = sqlx::query_as("SELECT motd, version, icon FROM fingerprints WHERE server = ?")
+            .bind(server)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.map(|(motd, version, icon)| Fingerprint { motd, version, icon }))
+    }
+
+    /// Stores the watched properties of `server`.
+    pub async fn store_fingerprint(&self, server: &str, fingerprint: &Fingerprint) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO fingerprints (server, motd, version, icon) VALUES (?, ?, ?, ?)
+             ON CONFLICT (server) DO UPDATE SET motd = excluded.motd, version = excluded.version, icon = excluded.icon",
+        )
+            .bind(server)
+            .bind(&fingerprint.motd)
+            .bind(&fingerprint.version)
+            .bind(&fingerprint.icon)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// The peaks of `server` as of `now`.
+    pub async fn peaks(&self, server: &str, now: SystemTime) -> Result<Peaks, sqlx::Error> {
+        let today = timestamp(now) / 86400;
+        Ok(Peaks {
+            today: self.peak(server, today).await?,
+            last_7d: self.peak(server, today - 6).await?,
+            all_time: self.peak(server, i64::MIN).await?,
+        })
+    }
+
+    /// The peak of `server` since the day `since` since the
+    /// Unix epoch, the earliest if there are several.
+    async fn peak(&self, server: &str, since: i64) -> Result<Option<Peak>, sqlx::Error> {
+        let row: Option<(i64, i64)> = sqlx::query_as("SELECT players, reached_at FROM peaks WHERE server = ? AND day >= ? ORDER BY players DESC, reached_at LIMIT 1")
+            .bind(server)
+            .bind(since)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.map(|(players, reached_at)| Peak { players: players.max(0) as u32, reached_at: reached_at.max(0) as u64 }))
+    }
+
+    /// The checks of `server` since `since`, oldest first.
+    pub async fn checks(&self, server: &str, since: SystemTime) -> Result<Vec<Check>, sqlx::Error> {
+        let rows: Vec<(i64, bool, bool)> = sqlx::query_as("SELECT checked_at, online, maintenance FROM checks WHERE server = ? AND checked_at >= ? ORDER BY checked_at")
+            .bind(server)
+            .bind(timestamp(since))
+            .fetch_all(&self.pool).await?;
+        Ok(rows.into_iter().map(|(checked_at, online, maintenance)| Check { checked_at: checked_at.max(0) as u64, online, maintenance }).collect())
+    }
+
+    /// The player counts of `server` between `from` and `to`
+    /// in seconds since the Unix epoch, one point for every
+    /// `step` seconds it was online in, oldest first.
+    pub async fn players(&self, server: &str, from: u64, to: u64, step: u64) -> Result<Vec<PlayersPoint>, sqlx::Error> {
+        let step = step.max(1) as i64;
+        let rows: Vec<(i64, f64, i64)> = sqlx::query_as(
+            "SELECT checked_at / ?1 * ?1 AS step, AVG(players_online), MAX(players_max) FROM checks
+             WHERE server = ?2 AND checked_at BETWEEN ?3 AND ?4 AND online
+             GROUP BY step ORDER BY step",
+        )
+            .bind(step)
+            .bind(server)
+            .bind(from as i64)
+            .bind(to as i64)
+            .fetch_all(&self.pool).await?;
+        Ok(rows.into_iter().map(|(timestamp, online, max)| PlayersPoint { timestamp: timestamp.max(0) as u64, online, max: max.max(0) as u32 }).collect())
+    }
+
+    /// Streams the records of `server` between `from` and `to`
+    /// in seconds since the Unix epoch, oldest first.
+    pub fn records(&self, server: String, from: u64, to: u64) -> impl Stream<Item = Result<Record, sqlx::Error>> + Send + '_ {
+        sqlx::query_as::<_, RecordRow>(
+            "SELECT checked_at, online, latency_ms, players_online, players_max, version, protocol, maintenance FROM checks
+             WHERE server = ? AND checked_at BETWEEN ? AND ? ORDER BY checked_at",
+        )
+            .bind(server)
+            .bind(from as i64)
+            .bind(to as i64)
+            .fetch(&self.pool)
+            .map(|x| x.map(|(checked_at, online, latency_ms, players_online, players_max, version, protocol, maintenance)| {
+                Record { checked_at, online, latency_ms, players_online, players_max, version, protocol, maintenance }
+            }))
+    }
+
+    /// The latest record of `server`, if it was checked yet.
+    pub async fn latest(&self, server: &str) -> Result<Option<Record>, sqlx::Error> {
+        let row: Option<RecordRow> = sqlx::query_as(
+            "SELECT checked_at, online, latency_ms, players_online, players_max, version, protocol, maintenance FROM checks
+             WHERE server = ? ORDER BY checked_at DESC LIMIT 1",
+        )
+            .bind(server)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.map(|(checked_at, online, latency_ms, players_online, players_max, version, protocol, maintenance)| {
+            Record { checked_at, online, latency_ms, players_online, players_max, version, protocol, maintenance }
+        }))
+    }
+
+    /// Waits for the running queries and closes the database.
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    /// Whether the database can be queried.
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await.map(|_| ())
+    }
+
+    /// When the incident of `server` that hasn't ended yet
+    /// started, if there is one.
+    pub async fn open_incident(&self, server: &str) -> Result<Option<SystemTime>, sqlx::Error> {
+        let started_at: Option<(i64,)> = sqlx::query_as("SELECT started_at FROM incidents WHERE server = ? AND ended_at IS NULL ORDER BY started_at DESC LIMIT 1")
+            .bind(server)
+            .fetch_optional(&self.pool).await?;
+        Ok(started_at.map(|(x,)| UNIX_EPOCH + Duration::from_secs(x.max(0) as u64)))
+    }
+
+    /// Opens an incident of `server` going offline at
+    /// `started_at` because of `error`.
+    pub async fn start_incident(&self, server: &str, started_at: SystemTime, error: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO incidents (server, started_at, error) VALUES (?, ?, ?)")
+            .bind(server)
+            .bind(timestamp(started_at))
+            .bind(error)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Ends the open incidents of `server` at `ended_at`.
+    pub async fn end_incident(&self, server: &str, ended_at: SystemTime) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE incidents SET ended_at = ? WHERE server = ? AND ended_at IS NULL")
+            .bind(timestamp(ended_at))
+            .bind(server)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Up to `limit` incidents of `server` after skipping
+    /// `offset`, newest first, and the total number of them.
+    pub async fn incidents(&self, server: &str, offset: u64, limit: u64) -> Result<(Vec<Incident>, u64), sqlx::Error> {
+        let rows: Vec<(i64, i64, Option<i64>, Option<String>)> = sqlx::query_as(
+            "SELECT id, started_at, ended_at, error FROM incidents WHERE server = ?
+             ORDER BY started_at DESC, id DESC LIMIT ? OFFSET ?",
+        )
+            .bind(server)
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .fetch_all(&self.pool).await?;
+        let (total,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM incidents WHERE server = ?")
+            .bind(server)
+            .fetch_one(&self.pool).await?;
+
+        let now = timestamp(SystemTime::now());
+        let incidents = rows.into_iter().map(|x| Incident::from_row(x, now)).collect();
+        Ok((incidents, total.max(0) as u64))
+    }
+
+    /// The `limit` incidents that started or ended last, of
+    /// `server` or of all servers, along with their server.
+    pub async fn latest_incidents(&self, server: Option<&str>, limit: u64) -> Result<Vec<(String, Incident)>, sqlx::Error> {
+        let rows: Vec<ServerIncidentRow> = sqlx::query_as(
+            "SELECT server, id, started_at, ended_at, error FROM incidents WHERE ?1 IS NULL OR server = ?1
+             ORDER BY COALESCE(ended_at, started_at) DESC, id DESC LIMIT ?2",
+        )
+            .bind(server)
+            .bind(limit as i64)
+            .fetch_all(&self.pool).await?;
+
+        let now = timestamp(SystemTime::now());
+        Ok(rows.into_iter()
+            .map(|(server, id, start, end, error)| (server, Incident::from_row((id, start, end, error), now)))
+            .collect())
+    }
+
+    /// The monitored servers added at runtime, as the JSON they
+    /// were stored as, in the order they were added.
+    pub async fn servers(&self) -> Result<Vec<String>, sqlx::Error> {
+        let rows: Vec<(String,)> = sqlx::query_as("SELECT config FROM servers ORDER BY rowid")
+            .fetch_all(&self.pool).await?;
+        Ok(rows.into_iter().map(|(x,)| x).collect())
+    }
+
+    /// Stores the JSON `config` of the monitored server at
+    /// `address`, replacing the one it had.
+    pub async fn store_server(&self, address: &str, config: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO servers (address, config) VALUES (?, ?) ON CONFLICT (address) DO UPDATE SET config = excluded.config")
+            .bind(address)
+            .bind(config)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Removes the monitored server at `address`, keeping its
+    /// checks and incidents.
+    pub async fn remove_server(&self, address: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM servers WHERE address = ?")
+            .bind(address)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Averages the results older than the retention of the
+    /// `config` over every downsampling interval, and removes
+    /// the downsampled ones past their own retention.
+    pub async fn compact(&self, config: &HistoryConfig) -> Result<(), sqlx::Error> {
+        let now = timestamp(SystemTime::now());
+        let mut transaction = self.pool.begin().await?;
+
+        if config.retention_days > 0 {
+            let step = (config.downsample_minutes * 60) as i64;
+            let cutoff = now - days(config.retention_days);
+            if step > 0 {
+                // Whole steps only, so none is averaged twice
+                let cutoff = cutoff / step * step;
+                sqlx::query(
+                    "INSERT INTO checks (server, checked_at, online, latency_ms, players_online, players_max, version, protocol, downsampled, maintenance)
+                     SELECT server, checked_at / ?1 * ?1 AS step, AVG(online) >= 0.5, CAST(AVG(latency_ms) AS INTEGER),
+                        CAST(ROUND(AVG(players_online)) AS INTEGER), MAX(players_max), MAX(version), MAX(protocol), 1, AVG(maintenance) >= 0.5
+                     FROM checks WHERE NOT downsampled AND checked_at < ?2
+                     GROUP BY server, step",
+                )
+                    .bind(step)
+                    .bind(cutoff)
+                    .execute(&mut *transaction).await?;
+                sqlx::query("DELETE FROM checks WHERE NOT downsampled AND checked_at < ?")
+                    .bind(cutoff)
+                    .execute(&mut *transaction).await?;
+            } else {
+                sqlx::query("DELETE FROM checks WHERE checked_at < ?")
+                    .bind(cutoff)
+                    .execute(&mut *transaction).await?;
+            }
+        }
+
+        if config.downsampled_retention_days > 0 {
+            sqlx::query("DELETE FROM checks WHERE downsampled AND checked_at < ?")
+                .bind(now - days(config.downsampled_retention_days))
+                .execute(&mut *transaction).await?;
+            sqlx::query("DELETE FROM sessions WHERE last_seen < ?")
+                .bind(now - days(config.downsampled_retention_days))
+                .execute(&mut *transaction).await?;
+        }
+
+        transaction.commit().await
+    }
+}
@@ -0,0 +1,501 @@
+//! Client side of the Java Edition
+//! [ServerListPing](https://wiki.vg/Server_List_Ping).
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde_json::{json, Map, Value};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::Instrument;
+use crate::chat::Chat;
+use crate::forge::{ForgeData, ForgeInfo, LegacyModInfo};
+use crate::proxy::{self, Proxy};
+use crate::PingError;
+
+/// The protocol version sent in the handshake by default, the
+/// one of 1.19.2.
+pub const DEFAULT_PROTOCOL_VERSION: i32 = 760;
+
+/// Packets bigger than this are rejected before allocating
+/// a buffer for them.
+const MAX_PACKET_LENGTH: usize = 1 << 21;
+
+/// How long to wait for the connection and the status by
+/// default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long to wait for the pong before giving up on the
+/// latency.
+const PONG_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Contains information about the server version.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerVersion {
+    /// The server's Minecraft version, i.e. "1.15.2".
+    pub name: String,
+
+    /// The server's ServerListPing protocol version.
+    pub protocol: i32,
+}
+
+/// Contains information about a player.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerPlayer {
+    /// The player's in-game name.
+    pub name: String,
+
+    /// The player's UUID.
+    pub id: String,
+}
+
+/// Contains information about the currently online
+/// players.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerPlayers {
+    /// The configured maximum number of players for the
+    /// server.
+    pub max: u32,
+
+    /// The number of players currently online.
+    pub online: u32,
+
+    /// An optional list of player information for
+    /// currently online players.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample: Option<Vec<ServerPlayer>>,
+}
+
+/// The decoded JSON response from a status query over
+/// ServerListPing.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StatusResponse {
+    /// Information about the server's version.
+    pub version: ServerVersion,
+
+    /// Information about currently online players.
+    pub players: ServerPlayers,
+
+    /// The server's MOTD as chat component, in the form the
+    /// server sent it.
+    #[serde(default)]
+    pub description: Chat,
+
+    /// The MOTD flattened to plain text.
+    #[serde(skip_deserializing)]
+    pub motd: String,
+
+    /// Optional field containing a path to the server's
+    /// favicon.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+
+    /// Whether the server requires chat messages to be
+    /// signed, since 1.19.1.
+    #[serde(rename = "enforcesSecureChat", default, skip_serializing_if = "Option::is_none")]
+    pub enforces_secure_chat: Option<bool>,
+
+    /// Whether the server sends chat previews, 1.19 to
+    /// 1.19.2 only.
+    #[serde(rename = "previewsChat", default, skip_serializing_if = "Option::is_none")]
+    pub previews_chat: Option<bool>,
+
+    /// The mod list of Forge servers.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub forge: Option<ForgeInfo>,
+
+    #[serde(rename = "forgeData", default, skip_serializing)]
+    pub forge_data: Option<ForgeData>,
+
+    #[serde(default, skip_serializing)]
+    pub modinfo: Option<LegacyModInfo>,
+
+    /// Fields not part of the vanilla response, i.e. from
+    /// proxies or server software.
+    #[serde(skip_deserializing, skip_serializing_if = "Map::is_empty")]
+    pub extra: Map<String, Value>,
+}
+
+impl StatusResponse {
+    /// Parses the JSON body of the status response packet.
+    ///
+    /// Should the body not match the vanilla format, it's
+    /// parsed again leniently, coercing the deviations common
+    /// with Spigot forks and proxies.
+    pub fn parse(body: &str) -> Result<StatusResponse, PingError> {
+        let mut value: Value = serde_json::from_str(body).map_err(|_| PingError::MalformedJson)?;
+        let mut status: StatusResponse = match serde_json::from_value(value.clone()) {
+            Ok(status) => status,
+            Err(_) => {
+                normalize(&mut value);
+                serde_json::from_value(value.clone()).map_err(|_| PingError::ProtocolError)?
+            }
+        };
+        if let Value::Object(object) = value {
+            status.extra = object.into_iter()
+                .filter(|(key, _)| !KNOWN_FIELDS.contains(&key.as_str()))
+                .collect();
+        }
+        status.motd = status.description.to_plain();
+        status.forge = ForgeInfo::from_status(status.forge_data.as_ref(), status.modinfo.as_ref());
+        Ok(status)
+    }
+}
+
+/// The fields of the status response that aren't collected
+/// into `extra`.
+const KNOWN_FIELDS: [&str; 8] = [
+    "version",
+    "players",
+    "description",
+    "favicon",
+    "enforcesSecureChat",
+    "previewsChat",
+    "forgeData",
+    "modinfo",
+];
+
+/// Coerces a status response into the vanilla format:
+/// numbers sent as strings are parsed, missing sections get
+/// defaults and broken optional sections are dropped.
+fn normalize(value: &mut Value) {
+    let object = match value.as_object_mut() {
+        Some(object) => object,
+        None => return,
+    };
+
+    let version = object.entry("version").or_insert_with(|| json!({}));
+    if !version.is_object() {
+        *version = json!({});
+    }
+    coerce_string(version, "name");
+    coerce_int(version, "protocol", -1, i32::MIN as i64, i32::MAX as i64);
+
+    let players = object.entry("players").or_insert_with(|| json!({}));
+    if !players.is_object() {
+        *players = json!({});
+    }
+    coerce_int(players, "max", 0, 0, u32::MAX as i64);
+    coerce_int(players, "online", 0, 0, u32::MAX as i64);
+    match players.get_mut("sample") {
+        Some(Value::Array(sample)) => {
+            sample.retain(|x| x.is_object());
+            for player in sample {
+                coerce_string(player, "name");
+                coerce_string(player, "id");
+            }
+        }
+        Some(_) => {
+            players.as_object_mut().map(|x| x.remove("sample"));
+        }
+        None => {}
+    }
+
+    if let Some(description) = object.get_mut("description") {
+        coerce_chat(description);
+    }
+    if let Some(favicon) = object.get("favicon") {
+        if !favicon.is_string() {
+            object.remove("favicon");
+        }
+    }
+    for (key, valid) in [
+        ("forgeData", object.get("forgeData").map(|x| serde_json::from_value::<ForgeData>(x.clone()).is_ok())),
+        ("modinfo", object.get("modinfo").map(|x| serde_json::from_value::<LegacyModInfo>(x.clone()).is_ok())),
+    ] {
+        if valid == Some(false) {
+            object.remove(key);
+        }
+    }
+}
+
+/// Makes `object[key]` a string, stringifying scalars.
+fn coerce_string(object: &mut Value, key: &str) {
+    let value = &mut object[key];
+    *value = match value {
+        Value::String(_) => return,
+        Value::Number(x) => Value::String(x.to_string()),
+        Value::Bool(x) => Value::String(x.to_string()),
+        _ => Value::String(String::new()),
+    };
+}
+
+/// Makes `object[key]` an integer within `min..=max`, parsing
+/// strings and truncating floats.
+fn coerce_int(object: &mut Value, key: &str, default: i64, min: i64, max: i64) {
+    let value = &mut object[key];
+    let number = match value {
+        Value::Number(x) => x.as_i64().or_else(|| x.as_f64().map(|x| x as i64)),
+        Value::String(x) => x.trim().parse::<i64>().ok().or_else(|| x.trim().parse::<f64>().ok().map(|x| x as i64)),
+        _ => None,
+    };
+    *value = json!(number.unwrap_or(default).clamp(min, max));
+}
+
+/// Stringifies the scalars within a chat component that
+/// should have been strings.
+fn coerce_chat(value: &mut Value) {
+    match value {
+        Value::Null => *value = Value::String(String::new()),
+        Value::Number(x) => *value = Value::String(x.to_string()),
+        Value::Bool(x) => *value = Value::String(x.to_string()),
+        Value::String(_) => {}
+        Value::Array(list) => list.iter_mut().for_each(coerce_chat),
+        Value::Object(object) => {
+            if let Some(text) = object.get_mut("text") {
+                if !text.is_string() {
+                    coerce_chat(text);
+                    if !text.is_string() {
+                        object.remove("text");
+                    }
+                }
+            }
+            for key in ["extra", "with"] {
+                match object.get_mut(key) {
+                    Some(Value::Array(list)) => list.iter_mut().for_each(coerce_chat),
+                    Some(_) => {
+                        object.remove(key);
+                    }
+                    None => {}
+                }
+            }
+            for key in ["bold", "italic", "underlined", "strikethrough", "obfuscated"] {
+                if let Some(flag) = object.get(key) {
+                    let flag = match flag {
+                        Value::Bool(x) => *x,
+                        Value::String(x) => x == "true",
+                        Value::Number(x) => x.as_i64() != Some(0),
+                        _ => false,
+                    };
+                    object.insert(key.to_string(), Value::Bool(flag));
+                }
+            }
+            if object.get("color").map(|x| !x.is_string()).unwrap_or(false) {
+                object.remove("color");
+            }
+        }
+    }
+}
+
+/// Options for the handshake of a ServerListPing.
+#[derive(Debug, Clone)]
+pub struct PingOptions {
+    /// The protocol version sent in the handshake. Servers
+    /// respond the same, but proxies like ViaVersion may
+    /// report a different version depending on it.
+    pub protocol: i32,
+
+    /// The server name sent in the handshake instead of the
+    /// connected host, for the forced hosts of BungeeCord and
+    /// Velocity.
+    pub vhost: Option<String>,
+
+    /// The SOCKS5 proxy to connect through.
+    pub proxy: Option<Proxy>,
+
+    /// How long to wait for the connection to open.
+    pub connect_timeout: Duration,
+
+    /// How long to wait for the status after connecting.
+    pub read_timeout: Duration,
+
+    /// Called with the `host:port` and the body of status
+    /// responses that couldn't be parsed, to report them.
+    pub on_invalid_status: Option<fn(&str, &str)>,
+}
+
+impl PingOptions {
+    /// The server name to send in the handshake when
+    /// connecting to `host`.
+    pub fn server_name<'a>(&'a self, host: &'a str) -> &'a str {
+        self.vhost.as_deref().unwrap_or(host)
+    }
+
+    /// The same options, sending `host` as server name unless
+    /// there is a different one already.
+    pub fn with_server_name(&self, host: &str) -> PingOptions {
+        PingOptions {
+            vhost: Some(self.server_name(host).to_string()),
+            ..self.clone()
+        }
+    }
+}
+
+impl Default for PingOptions {
+    fn default() -> Self {
+        PingOptions {
+            protocol: DEFAULT_PROTOCOL_VERSION,
+            vhost: None,
+            proxy: None,
+            connect_timeout: DEFAULT_TIMEOUT,
+            read_timeout: DEFAULT_TIMEOUT,
+            on_invalid_status: None,
+        }
+    }
+}
+
+/// The outcome of a successful ServerListPing.
+#[derive(Debug, Clone)]
+pub struct Ping {
+    /// The decoded status response.
+    pub status: StatusResponse,
+
+    /// The round-trip time of the ping packet, if the server
+    /// answered it.
+    pub latency: Option<Duration>,
+
+    /// Whether the pong echoed the payload of the ping packet
+    /// as the protocol requires, if the server answered it.
+    pub payload_verified: Option<bool>,
+}
+
+/// Performs the handshake, requests the status and measures
+/// the latency with a ping packet afterwards.
+pub async fn ping(host: &str, port: u16, options: &PingOptions) -> Result<Ping, PingError> {
+    let mut stream = connect(host, port, options).await?;
+    let body = tokio::time::timeout(options.read_timeout, request_status(&mut stream, host, port, options)).await
+        .map_err(|_| PingError::ReadTimeout)??;
+    let status = StatusResponse::parse(&body).inspect_err(|_| {
+        if let Some(report) = options.on_invalid_status {
+            report(&format!("{}:{}", host, port), &body);
+        }
+    })?;
+
+    // Not every server answers the ping, which shouldn't make
+    // the whole status fail
+    let pong = tokio::time::timeout(PONG_TIMEOUT, measure_latency(&mut stream).instrument(tracing::info_span!("latency"))).await
+        .ok()
+        .and_then(|x| x.ok());
+
+    Ok(Ping {
+        status,
+        latency: pong.map(|x| x.0),
+        payload_verified: pong.map(|x| x.1),
+    })
+}
+
+/// Requests the status and returns the JSON body exactly as
+/// the server sent it.
+pub async fn ping_raw(host: &str, port: u16, options: &PingOptions) -> Result<String, PingError> {
+    let mut stream = connect(host, port, options).await?;
+    tokio::time::timeout(options.read_timeout, request_status(&mut stream, host, port, options)).await
+        .map_err(|_| PingError::ReadTimeout)?
+}
+
+/// Opens the connection, through the proxy if there is one,
+/// waiting up to the connect timeout.
+pub async fn connect(host: &str, port: u16, options: &PingOptions) -> Result<TcpStream, PingError> {
+    let span = tracing::info_span!("connect", host, port, proxy = options.proxy.is_some());
+    tokio::time::timeout(options.connect_timeout, proxy::connect(host, port, options.proxy.as_ref())).instrument(span).await
+        .map_err(|_| PingError::ConnectTimeout)?
+}
+
+/// Sends the handshake and the status request and reads the
+/// body of the response.
+#[tracing::instrument(name = "handshake", skip_all, fields(protocol = options.protocol))]
+async fn request_status(stream: &mut TcpStream, host: &str, port: u16, options: &PingOptions) -> Result<String, PingError> {
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, options.protocol);
+    write_string(&mut handshake, options.server_name(host));
+    handshake.extend_from_slice(&port.to_be_bytes());
+    write_varint(&mut handshake, 1);
+
+    let mut buffer = packet(0x00, &handshake);
+    buffer.extend_from_slice(&packet(0x00, &[]));
+    stream.write_all(&buffer).await.map_err(|e| PingError::io(&e))?;
+
+    let response = read_packet(stream, 0x00).await?;
+    read_string(&mut response.as_slice()).await
+}
+
+/// Sends the ping packet with the current time as payload and
+/// waits for the pong. Returns the round-trip time and whether
+/// the pong carried the same payload, which fake "always
+/// online" proxies often get wrong.
+async fn measure_latency(stream: &mut TcpStream) -> Result<(Duration, bool), PingError> {
+    let payload = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    let start = Instant::now();
+    stream.write_all(&packet(0x01, &payload.to_be_bytes())).await.map_err(|e| PingError::io(&e))?;
+    let pong = read_packet(stream, 0x01).await?;
+    let latency = start.elapsed();
+
+    Ok((latency, pong == payload.to_be_bytes()))
+}
+
+/// Frames `data` as a packet with the given ID.
+fn packet(id: i32, data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(data.len() + 1);
+    write_varint(&mut body, id);
+    body.extend_from_slice(data);
+
+    let mut packet = Vec::with_capacity(body.len() + 5);
+    write_varint(&mut packet, body.len() as i32);
+    packet.extend_from_slice(&body);
+    packet
+}
+
+/// Reads a packet, checks its ID and returns the remaining
+/// data.
+async fn read_packet(stream: &mut TcpStream, id: i32) -> Result<Vec<u8>, PingError> {
+    let length = read_varint(stream).await.map_err(|_| PingError::ConnectionReset)? as usize;
+    if length == 0 || length > MAX_PACKET_LENGTH {
+        return Err(PingError::UnexpectedPacket);
+    }
+    let mut buffer = vec![0; length];
+    stream.read_exact(&mut buffer).await.map_err(|e| PingError::io(&e))?;
+
+    let mut data = buffer.as_slice();
+    if read_varint(&mut data).await? != id {
+        return Err(PingError::UnexpectedPacket);
+    }
+    let offset = buffer.len() - data.len();
+    buffer.drain(..offset);
+    Ok(buffer)
+}
+
+fn write_varint(buffer: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            buffer.push(value as u8);
+            return;
+        }
+        buffer.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+fn write_string(buffer: &mut Vec<u8>, string: &str) {
+    write_varint(buffer, string.len() as i32);
+    buffer.extend_from_slice(string.as_bytes());
+}
+
+async fn read_varint<R: AsyncRead + Unpin>(reader: &mut R) -> Result<i32, PingError> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let byte = reader.read_u8().await.map_err(|_| PingError::ProtocolError)?;
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(PingError::ProtocolError)
+}
+
+async fn read_string(data: &mut &[u8]) -> Result<String, PingError> {
+    let length = read_varint(data).await? as usize;
+    let string = data.get(..length).ok_or(PingError::ProtocolError)?;
+    Ok(decode(string))
+}
+
+/// Decodes a string that should be UTF-8. Some Chinese and old
+/// cracked servers send GBK or Latin-1 though, so whichever
+/// decodes without errors first is used, Latin-1 always does.
+fn decode(bytes: &[u8]) -> String {
+    if let Ok(string) = std::str::from_utf8(bytes) {
+        return string.to_string();
+    }
+    if let Some(string) = encoding_rs::GBK.decode_without_bom_handling_and_without_replacement(bytes) {
+        return string.into_owned();
+    }
+    encoding_rs::WINDOWS_1252.decode_without_bom_handling(bytes).0.into_owned()
+}
@@ -0,0 +1,127 @@
+//! The [legacy server list ping](https://wiki.vg/Server_List_Ping#1.6)
+//! understood by servers from Beta 1.8 up to 1.6.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use crate::chat::{strip_codes, Chat};
+use crate::java::{self, PingOptions, ServerPlayers, ServerVersion, StatusResponse};
+use crate::PingError;
+
+/// The protocol number sent in the `MC|PingHost` plugin
+/// message, the one of 1.6.4.
+const LEGACY_PROTOCOL_VERSION: u8 = 78;
+
+/// Sends the `0xFE` ping to `host:port` and normalizes the
+/// kick message into a regular `StatusResponse`. Only the
+/// server name, proxy and timeouts of the `options` apply.
+#[tracing::instrument(name = "legacy_ping", skip(options))]
+pub async fn ping_legacy(host: &str, port: u16, options: &PingOptions) -> Result<StatusResponse, PingError> {
+    let mut stream = java::connect(host, port, options).await?;
+    let message = tokio::time::timeout(options.read_timeout, read_kick(&mut stream, options.server_name(host), port)).await
+        .map_err(|_| PingError::ReadTimeout)??;
+    parse_kick(&message)
+}
+
+async fn read_kick(stream: &mut TcpStream, server_name: &str, port: u16) -> Result<String, PingError> {
+    stream.write_all(&ping_packet(server_name, port)).await.map_err(|e| PingError::io(&e))?;
+
+    let packet_id = stream.read_u8().await.map_err(|e| PingError::io(&e))?;
+    if packet_id != 0xFF {
+        return Err(PingError::UnexpectedPacket);
+    }
+    let len = stream.read_u16().await.map_err(|e| PingError::io(&e))? as usize;
+    let mut buffer = vec![0u8; len * 2];
+    stream.read_exact(&mut buffer).await.map_err(|e| PingError::io(&e))?;
+
+    let units: Vec<u16> = buffer.chunks_exact(2).map(|x| u16::from_be_bytes([x[0], x[1]])).collect();
+    Ok(String::from_utf16_lossy(&units))
+}
+
+/// Builds the 1.6 ping: `0xFE 0x01` followed by a
+/// `MC|PingHost` plugin message. Servers before 1.6 stop
+/// reading after the first bytes and answer all the same.
+fn ping_packet(host: &str, port: u16) -> Vec<u8> {
+    let mut data = vec![LEGACY_PROTOCOL_VERSION];
+    write_utf16(&mut data, host);
+    data.extend_from_slice(&(port as i32).to_be_bytes());
+
+    let mut packet = vec![0xFE, 0x01, 0xFA];
+    write_utf16(&mut packet, "MC|PingHost");
+    packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&data);
+    packet
+}
+
+fn write_utf16(buffer: &mut Vec<u8>, string: &str) {
+    let units: Vec<u16> = string.encode_utf16().collect();
+    buffer.extend_from_slice(&(units.len() as u16).to_be_bytes());
+    for unit in units {
+        buffer.extend_from_slice(&unit.to_be_bytes());
+    }
+}
+
+/// Parses the kick message, either the 1.4+ form
+/// `§1\0protocol\0version\0motd\0online\0max` or the older
+/// `motd§online§max`.
+fn parse_kick(message: &str) -> Result<StatusResponse, PingError> {
+    let (protocol, version, motd, online, max) = if let Some(rest) = message.strip_prefix("\u{a7}1\0") {
+        let fields: Vec<&str> = rest.split('\0').collect();
+        if fields.len() != 5 {
+            return Err(PingError::ProtocolError);
+        }
+        (fields[0].parse().map_err(|_| PingError::ProtocolError)?, fields[1], fields[2], fields[3], fields[4])
+    } else {
+        let mut fields = message.rsplitn(3, '\u{a7}');
+        let max = fields.next().ok_or(PingError::ProtocolError)?;
+        let online = fields.next().ok_or(PingError::ProtocolError)?;
+        let motd = fields.next().ok_or(PingError::ProtocolError)?;
+        (0, "Beta 1.8-1.3", motd, online, max)
+    };
+
+    Ok(StatusResponse {
+        version: ServerVersion {
+            name: version.to_string(),
+            protocol,
+        },
+        players: ServerPlayers {
+            max: max.parse().map_err(|_| PingError::ProtocolError)?,
+            online: online.parse().map_err(|_| PingError::ProtocolError)?,
+            sample: None,
+        },
+        description: Chat::Text(motd.to_string()),
+        motd: strip_codes(motd),
+        favicon: None,
+        enforces_secure_chat: None,
+        previews_chat: None,
+        forge: None,
+        forge_data: None,
+        modinfo: None,
+        extra: Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_kicks() {
+        let status = parse_kick("\u{a7}1\u{0}127\u{0}1.6.4\u{0}A \u{a7}aMinecraft\u{a7}r Server\u{0}3\u{0}20").unwrap();
+        assert_eq!(status.version.protocol, 127);
+        assert_eq!(status.version.name, "1.6.4");
+        assert_eq!((status.players.online, status.players.max), (3, 20));
+        assert_eq!(status.motd, "A Minecraft Server");
+
+        let status = parse_kick("A Minecraft Server\u{a7}3\u{a7}20").unwrap();
+        assert_eq!(status.version.protocol, 0);
+        assert_eq!((status.players.online, status.players.max), (3, 20));
+        assert_eq!(status.motd, "A Minecraft Server");
+    }
+
+    #[test]
+    fn refuses_truncated_kicks() {
+        for message in ["", "\u{a7}1\u{0}", "\u{a7}1\u{0}127\u{0}1.6.4\u{0}A Minecraft Server\u{0}3", "A Minecraft Server\u{a7}3", "A Minecraft Server\u{a7}3\u{a7}"] {
+            assert!(matches!(parse_kick(message), Err(PingError::ProtocolError)), "{:?}", message);
+        }
+    }
+}
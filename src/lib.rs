@@ -0,0 +1,256 @@
+//! Checks the status of Minecraft servers: Java Edition
+//! servers with the ServerListPing and the legacy ping,
+//! Bedrock Edition servers and the query protocol, along with
+//! the resolution of their addresses and a cache of their
+//! statuses. The checker serves these over HTTP, other
+//! programs can use them without it:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), mcstatus_checker::PingError> {
+//! use hickory_resolver::TokioAsyncResolver;
+//! use mcstatus_checker::java::PingOptions;
+//!
+//! let resolver = TokioAsyncResolver::tokio(Default::default(), Default::default());
+//! let (target, ping) = mcstatus_checker::check_java(&resolver, "mc.example.com", &PingOptions::default(), 3).await?;
+//! println!("{}:{} has {} players online", target.host, target.port, ping.status.players.online);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod bedrock;
+pub mod cache;
+pub mod chat;
+pub mod dns;
+pub mod forge;
+pub mod java;
+pub mod legacy;
+pub mod proxy;
+pub mod query;
+
+use std::io;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+use hickory_resolver::TokioAsyncResolver;
+use thiserror::Error;
+use crate::bedrock::BedrockStatus;
+use crate::dns::Target;
+use crate::java::{Ping, PingOptions};
+use crate::query::QueryStatus;
+
+/// The port of Java Edition servers if the address has none.
+pub const JAVA_PORT: u16 = 25565;
+
+/// The port of Bedrock Edition servers if the address has
+/// none.
+pub const BEDROCK_PORT: u16 = 19132;
+
+/// Why a server couldn't be pinged.
+#[derive(Error, Debug, Clone)]
+pub enum PingError {
+    #[error("Protocol error")]
+    ProtocolError,
+
+    #[error("The server responded a status that isn't valid JSON")]
+    MalformedJson,
+
+    #[error("The server responded an unexpected packet")]
+    UnexpectedPacket,
+
+    #[error("The connection was closed before the status was read")]
+    ConnectionReset,
+
+    #[error("The domain doesn't exist or has no address")]
+    UnknownHost,
+
+    #[error("The domain couldn't be resolved")]
+    DnsError,
+
+    #[error("The server refused the connection, it is likely offline")]
+    ConnectionRefused,
+
+    #[error("The network of the server can't be reached")]
+    Unreachable,
+
+    #[error("Invalid input string")]
+    InvalidInput,
+
+    #[error("Timed out")]
+    Timeout,
+
+    #[error("Timed out connecting")]
+    ConnectTimeout,
+
+    #[error("Timed out waiting for the status")]
+    ReadTimeout,
+}
+
+impl PingError {
+    /// The error of a failed connection, read or write.
+    pub fn io(e: &io::Error) -> PingError {
+        match e.kind() {
+            io::ErrorKind::ConnectionRefused => PingError::ConnectionRefused,
+            io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable => PingError::Unreachable,
+            io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::BrokenPipe | io::ErrorKind::UnexpectedEof => PingError::ConnectionReset,
+            io::ErrorKind::TimedOut => PingError::Timeout,
+            _ => PingError::ProtocolError,
+        }
+    }
+
+    /// Whether the server may not understand the protocol, as
+    /// it answered with something else or hung up. Servers that
+    /// couldn't be reached or timed out won't answer another
+    /// protocol either.
+    fn mismatched(&self) -> bool {
+        matches!(self, PingError::ProtocolError | PingError::UnexpectedPacket | PingError::ConnectionReset)
+    }
+}
+
+/// Splits `address` into host and port, accepting
+/// `host`, `host:port`, bare IPv6 literals and bracketed ones
+/// like `[::1]:25565`. The `port` query parameter may be used
+/// instead of a port in the address. Invalid ports, or two
+/// different ones, are an `InvalidInput`.
+pub fn parse_address(address: &str, port: Option<u16>) -> Result<(&str, Option<u16>), PingError> {
+    let (host, address_port) = if let Some(rest) = address.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']').ok_or(PingError::InvalidInput)?;
+        if host.parse::<Ipv6Addr>().is_err() {
+            return Err(PingError::InvalidInput);
+        }
+        match rest {
+            "" => (host, None),
+            _ => (host, Some(rest.strip_prefix(':').ok_or(PingError::InvalidInput)?)),
+        }
+    } else if address.parse::<Ipv6Addr>().is_ok() {
+        (address, None)
+    } else {
+        match address.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (address, None),
+        }
+    };
+
+    if host.is_empty() {
+        return Err(PingError::InvalidInput);
+    }
+    let address_port = match address_port {
+        Some(x) => Some(x.parse::<u16>().map_err(|_| PingError::InvalidInput)?),
+        None => None,
+    };
+    match (address_port, port) {
+        (Some(a), Some(b)) if a != b => Err(PingError::InvalidInput),
+        (a, b) => Ok((host, a.or(b))),
+    }
+}
+
+/// Resolves `host` without following SRV records, failing
+/// if it can't be.
+async fn resolve(resolver: &TokioAsyncResolver, host: &str, port: u16) -> Result<Target, PingError> {
+    let target = dns::resolve(resolver, host, Some(port)).await;
+    match target.unresolved {
+        Some(e) => Err(e),
+        None => Ok(target),
+    }
+}
+
+/// Resolves `address` like the vanilla client, following
+/// the SRV record if it has no port, and pings the Java
+/// Edition server it points to, trying `retry` times.
+/// Servers before 1.7 are pinged with the legacy ping.
+pub async fn check_java(resolver: &TokioAsyncResolver, address: &str, options: &PingOptions, retry: usize) -> Result<(Target, Ping), PingError> {
+    let (host, port) = parse_address(address, None)?;
+    let target = dns::resolve(resolver, host, port).await;
+    // Proxies resolve the host themselves
+    if let (Some(e), None) = (&target.unresolved, &options.proxy) {
+        return Err(e.clone());
+    }
+    let options = options.with_server_name(&target.host);
+    let ping = ping_java(&target.tcp_host(options.proxy.as_ref()), target.port, &options, retry).await?;
+    Ok((target, ping))
+}
+
+/// Resolves `address` and pings the Bedrock Edition server,
+/// waiting up to `timeout` for each of `retry` tries.
+pub async fn check_bedrock(resolver: &TokioAsyncResolver, address: &str, timeout: Duration, retry: usize) -> Result<(Target, BedrockStatus), PingError> {
+    let (host, port) = parse_address(address, None)?;
+    let target = resolve(resolver, host, port.unwrap_or(BEDROCK_PORT)).await?;
+    let status = bedrock::ping_bedrock(&target.connect_host(), target.port, timeout, retry).await?;
+    Ok((target, status))
+}
+
+/// Resolves `address` and queries the server, only for the
+/// basic stat if `basic`, waiting up to `timeout` for each
+/// of `retry` tries. The query has to be enabled in the
+/// `server.properties` of the server.
+pub async fn check_query(resolver: &TokioAsyncResolver, address: &str, basic: bool, timeout: Duration, retry: usize) -> Result<(Target, QueryStatus), PingError> {
+    let (host, port) = parse_address(address, None)?;
+    let target = resolve(resolver, host, port.unwrap_or(JAVA_PORT)).await?;
+    let status = query::query(&target.connect_host(), target.port, basic, timeout, retry).await?;
+    Ok((target, status))
+}
+
+/// Pings the Java Edition server at `host:port`, trying
+/// `retry` times before responding the last error.
+pub async fn ping_java(host: &str, port: u16, options: &PingOptions, retry: usize) -> Result<Ping, PingError> {
+    let mut last_err = PingError::ProtocolError;
+    for _ in 0..retry {
+        match ping_timeout(host, port, options).await {
+            Ok(x) => {
+                return Ok(x);
+            }
+            Err(e) => {
+                last_err = e;
+            }
+        }
+    }
+    Err(last_err)
+}
+
+/// Pings with the connect and read timeouts of the
+/// `options`, falling back to the legacy ping if the server
+/// doesn't seem to understand the modern one.
+async fn ping_timeout(host: &str, port: u16, options: &PingOptions) -> Result<Ping, PingError> {
+    match java::ping(host, port, options).await {
+        Ok(y) => {
+            Ok(y)
+        }
+        Err(e) if !e.mismatched() => {
+            Err(e)
+        }
+        Err(e) => {
+            // Servers before 1.7 don't understand the modern handshake
+            match legacy::ping_legacy(host, port, options).await {
+                Ok(status) => Ok(Ping { status, latency: None, payload_verified: None }),
+                Err(_) => Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hosts_and_ports() {
+        assert!(matches!(parse_address("mc.example.com", None), Ok(("mc.example.com", None))));
+        assert!(matches!(parse_address("mc.example.com:25566", None), Ok(("mc.example.com", Some(25566)))));
+        assert!(matches!(parse_address("mc.example.com", Some(25566)), Ok(("mc.example.com", Some(25566)))));
+        assert!(matches!(parse_address("mc.example.com:25566", Some(25566)), Ok(("mc.example.com", Some(25566)))));
+    }
+
+    #[test]
+    fn parses_ipv6() {
+        assert!(matches!(parse_address("::1", None), Ok(("::1", None))));
+        assert!(matches!(parse_address("2001:db8::1", None), Ok(("2001:db8::1", None))));
+        assert!(matches!(parse_address("[::1]", None), Ok(("::1", None))));
+        assert!(matches!(parse_address("[2001:db8::1]:25566", None), Ok(("2001:db8::1", Some(25566)))));
+    }
+
+    #[test]
+    fn refuses_invalid_addresses() {
+        for address in ["", ":25565", "mc.example.com:", "mc.example.com:abc", "mc.example.com:65536", "[::1", "[::1]25565", "[example.com]:25565"] {
+            assert!(matches!(parse_address(address, None), Err(PingError::InvalidInput)), "{}", address);
+        }
+        assert!(matches!(parse_address("mc.example.com:25565", Some(25566)), Err(PingError::InvalidInput)));
+    }
+}
@@ -0,0 +1,185 @@
+//! Structured logs of the requests and pings with `tracing`,
+//! as text or JSON lines, and their spans exported to an
+//! OpenTelemetry collector. Also writes the access log.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime};
+use opentelemetry::{global, KeyValue};
+use opentelemetry::trace::{TraceError, TracerProvider as _};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, Resource};
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use rocket::{Data, Orbit, Request, Response, Rocket};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::serde::json::serde_json::json;
+use tracing::Span;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use crate::config::{AccessLogFormat, Config, LogConfig, OtlpConfig};
+use crate::{date, unix_time};
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// The protocol of the request line of the access log. Rocket
+/// doesn't tell the version of a request, and only speaks
+/// HTTP/2 with TLS clients negotiating it.
+const PROTOCOL: &str = "HTTP/1.1";
+
+/// Installs the subscriber of `config`, which also receives
+/// the logs of Rocket and the other crates logging with
+/// `log`. Does nothing if one is installed already. Has to
+/// be called within the runtime if spans are exported.
+pub fn init(config: &LogConfig) {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&config.level))
+        .unwrap_or_else(|e| {
+            eprintln!("invalid log level {:?}, logging at info: {}", config.level, e);
+            EnvFilter::new("info")
+        });
+    let output = match config.json {
+        true => fmt::layer().json().with_current_span(true).with_span_list(false).boxed(),
+        false => fmt::layer().boxed(),
+    };
+    let otlp = config.otlp.as_ref()
+        .and_then(|x| tracer(x).map_err(|e| eprintln!("can't export spans to {}: {}", x.endpoint, e)).ok())
+        .map(|x| tracing_opentelemetry::layer().with_tracer(x));
+    let _ = tracing_subscriber::registry().with(filter).with(output).with(otlp).try_init();
+}
+
+/// The tracer exporting spans in batches to the collector of
+/// `config`, installed as the global provider to flush it on
+/// shutdown.
+fn tracer(config: &OtlpConfig) -> Result<Tracer, TraceError> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&config.endpoint)
+        .build()?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", config.service_name.clone())]))
+        .build();
+    let tracer = provider.tracer("mcstatus-checker");
+    global::set_tracer_provider(provider);
+    Ok(tracer)
+}
+
+/// The span of a request and when it arrived.
+struct RequestSpan(Span, Instant);
+
+/// Numbers the requests to tell their logs apart.
+static REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The time of the access log like `10/Oct/2000:13:55:36
+/// +0000`, in UTC.
+fn clf_time(secs: u64) -> String {
+    let (days, time) = ((secs / 86400) as i64, secs % 86400);
+    let (year, month, day) = date::civil(days);
+    format!("{:02}/{}/{}:{:02}:{:02}:{:02} +0000", day, MONTHS[month as usize - 1], year, time / 3600, time / 60 % 60, time % 60)
+}
+
+/// The server a request checks, from the address of the
+/// route or the `target` of `/probe`.
+fn target<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    let route = request.route()?;
+    if route.uri.unmounted_origin.path().as_str().starts_with("/<address>") {
+        request.param::<&str>(0)?.ok()
+    } else {
+        request.query_value::<&str>("target")?.ok()
+    }
+}
+
+/// Writes the line of a request to the access log.
+fn access_log(format: AccessLogFormat, request: &Request<'_>, response: &Response<'_>, latency_ms: f64) {
+    let now = unix_time(SystemTime::now());
+    let client = request.client_ip().map(|x| x.to_string());
+    let target = target(request);
+    let cache = response.headers().get_one("X-Cache");
+    let online = response.headers().get_one("X-MC-Online");
+    let bytes = response.body().preset_size();
+    match format {
+        AccessLogFormat::Off => {}
+        AccessLogFormat::Common => {
+            println!(
+                "{} - - [{}] \"{} {} {}\" {} {} {:?} {} {} {:.0}",
+                client.as_deref().unwrap_or("-"),
+                clf_time(now),
+                request.method(),
+                request.uri(),
+                PROTOCOL,
+                response.status().code,
+                bytes.map_or("-".to_string(), |x| x.to_string()),
+                target.unwrap_or("-"),
+                cache.unwrap_or("-"),
+                online.unwrap_or("-"),
+                latency_ms,
+            );
+        }
+        AccessLogFormat::Json => {
+            let line = json!({
+                "time": now,
+                "client": client,
+                "method": request.method().as_str(),
+                "uri": request.uri().to_string(),
+                "status": response.status().code,
+                "bytes": bytes,
+                "target": target,
+                "cache": cache,
+                "online": online.map(|x| x == "true"),
+                "duration_ms": latency_ms,
+                "user_agent": request.headers().get_one("User-Agent"),
+            });
+            println!("{}", line);
+        }
+    }
+}
+
+/// Logs every request with its route, status and how long it
+/// took, within a span of its method, URI and client, and
+/// writes the access log if enabled. Exports the remaining
+/// spans on shutdown.
+pub struct RequestTracing;
+
+#[rocket::async_trait]
+impl Fairing for RequestTracing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Tracing",
+            kind: Kind::Request | Kind::Response | Kind::Shutdown,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let span = tracing::info_span!(
+            "request",
+            id = REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+            method = %request.method(),
+            uri = %request.uri(),
+            client = request.client_ip().map(tracing::field::display),
+        );
+        request.local_cache(|| RequestSpan(span, Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let RequestSpan(span, start) = request.local_cache(|| RequestSpan(Span::none(), Instant::now()));
+        let _enter = span.enter();
+        let route = request.route().and_then(|x| x.name.as_deref()).unwrap_or("none");
+        let status = response.status().code;
+        let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+        match status {
+            500.. => tracing::error!(route, status, latency_ms, "responded"),
+            400.. => tracing::warn!(route, status, latency_ms, "responded"),
+            _ => tracing::info!(route, status, latency_ms, "responded"),
+        }
+
+        let format = request.rocket().state::<Config>().map_or(AccessLogFormat::Off, |x| x.log.access);
+        if format != AccessLogFormat::Off {
+            access_log(format, request, response, latency_ms);
+        }
+    }
+
+    async fn on_shutdown(&self, _: &Rocket<Orbit>) {
+        // Flushing blocks until the last batch is exported
+        let _ = tokio::task::spawn_blocking(global::shutdown_tracer_provider).await;
+    }
+}
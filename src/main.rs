@@ -1,220 +1,1497 @@
-#[macro_use]
-extern crate serde;
-
 #[macro_use]
 extern crate rocket;
 
-use std::future::Future;
+mod about;
+mod auth;
+mod badge;
+mod banner;
+mod catchers;
+mod changes;
+mod cli;
+mod compression;
+mod conditional;
+mod config;
+mod cors;
+mod cron;
+mod date;
+mod discord;
+mod error;
+mod favicon;
+mod feed;
+mod fields;
+mod format;
+mod groups;
+mod health;
+mod filter;
+mod headers;
+mod history;
+mod logging;
+mod maintenance;
+mod metrics;
+mod monitor;
+mod mqtt;
+mod notify;
+mod problem;
+mod ratelimit;
+mod report;
+mod security;
+mod servers;
+mod slack;
+#[cfg(unix)]
+mod systemd;
+mod telegram;
+mod template;
+mod uptime;
+mod versions;
+
+use std::net::IpAddr;
+use std::process::ExitCode;
+use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-use async_minecraft_ping::{ConnectionConfig, ServerDescription, ServerPlayer, ServerPlayers, ServerVersion, StatusResponse};
-use rocket::{Build, Rocket};
+use futures::future::{self, select_ok, FutureExt};
+use futures::stream::{self, Stream, StreamExt};
+use hickory_resolver::TokioAsyncResolver;
+use rocket::{Build, Either, Rocket, Route, State};
+use rocket::data::{ByteUnit, Data};
+use rocket::figment::{Figment, Profile};
+use rocket::figment::providers::{Env, Format, Toml};
 use rocket::http::{ContentType, Header, Status};
-use rocket::serde::json::Json;
-use serde::{Serialize, Serializer};
-use tokio::fs::read_to_string;
-use thiserror::Error;
-use tokio::time::Timeout;
-use serde_with::{serde_as, SerializeAs};
+use rocket::http::uri::Origin;
+use rocket::response::content::RawJson;
+use rocket::response::stream::TextStream;
+use rocket::serde::json::{serde_json, Json, Value};
+use serde::Serialize;
+use tracing::Instrument;
+use mcstatus_checker::{bedrock, cache, dns, java, parse_address, ping_java, query};
+use mcstatus_checker::bedrock::BedrockStatus;
+use mcstatus_checker::cache::{CacheKey, Checked, StatusCache};
+use mcstatus_checker::dns::Target;
+use mcstatus_checker::java::{PingOptions, ServerPlayers, ServerVersion, StatusResponse};
+use mcstatus_checker::query::QueryStatus;
+use crate::about::{About, Started};
+use crate::auth::{Admin, ApiKeys};
+use crate::banner::BannerRenderer;
+use crate::cli::Command;
+use crate::compression::Compression;
+use crate::conditional::ConditionalGet;
+use crate::config::{Config, MonitoredServer};
+use crate::cors::Cors;
+use crate::error::StatusError;
+use crate::filter::TargetFilter;
+use crate::format::{Formatted, V1};
+use crate::groups::{GroupServer, GroupStatus};
+use crate::headers::WithHeaders;
+use crate::health::Readiness;
+use crate::history::{History, Incident, Peaks, PlayersPoint, Session};
+use crate::logging::RequestTracing;
+use crate::metrics::{Metrics, RequestMetrics};
+use crate::problem::ProblemStatuses;
+use crate::monitor::{Heartbeat, Monitor, MonitorSettings};
+use crate::ratelimit::{ClientLimit, RateLimitHeaders, RateLimiter, RetryAfter, TargetLimiter};
+use crate::security::SecurityHeaders;
+use crate::servers::MonitoredServers;
+use crate::uptime::Uptime;
+use crate::versions::VersionProbe;
+
+/// The state shared by all checks of Java Edition servers.
+/// Clones share their cache and limits.
+#[derive(Clone)]
+pub struct Checker {
+    resolver: TokioAsyncResolver,
+    cache: Arc<StatusCache<StatusError>>,
 
-type StdError = Box<dyn std::error::Error>;
+    /// Limits how often the same server is pinged.
+    targets: TargetLimiter,
+
+    /// Refuses servers that may not be checked.
+    filter: Arc<TargetFilter>,
+
+    metrics: Arc<Metrics>,
+}
 
-/// Contains information about the server version.
 #[derive(Debug, Serialize)]
-#[serde(remote = "ServerVersion")]
-pub struct ServerVersionDef {
-    /// The server's Minecraft version, i.e. "1.15.2".
-    pub name: String,
+pub struct Response {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<StatusResponse>,
 
-    /// The server's ServerListPing protocol version.
-    pub protocol: u32,
+    /// The round-trip time of the ping packet in
+    /// milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency: Option<u64>,
+
+    /// Whether the server echoed the payload of the ping
+    /// packet correctly.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_verified: Option<bool>,
+
+    /// When the server was pinged for this status, in seconds
+    /// since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<u64>,
+
+    /// Whether the status was taken from the cache.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached: Option<bool>,
+
+    /// Whether the server couldn't be pinged and this is the
+    /// last status it responded instead.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+
+    /// How old the status is in milliseconds, 0 if it's from
+    /// this request's ping.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_age_ms: Option<u64>,
+
+    /// The host and port that were actually connected to,
+    /// after following any SRV record.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<Target>,
 }
 
-/// Contains information about a player.
+impl Response {
+    fn from_checked(checked: Checked, target: Option<Target>) -> Response {
+        Response {
+            err: None,
+            result: Some(checked.ping.status),
+            latency: checked.ping.latency.map(|x| x.as_millis() as u64),
+            payload_verified: checked.ping.payload_verified,
+            checked_at: checked.checked_at.duration_since(UNIX_EPOCH).ok().map(|x| x.as_secs()),
+            cached: Some(checked.age.is_some()),
+            stale: checked.stale,
+            cache_age_ms: Some(checked.age.map_or(0, |x| x.as_millis() as u64)),
+            target,
+        }
+    }
+
+    fn error(err: StatusError, target: Option<Target>) -> Response {
+        Response {
+            err: Some(err),
+            result: None,
+            latency: None,
+            payload_verified: None,
+            checked_at: None,
+            cached: None,
+            stale: false,
+            cache_age_ms: None,
+            target,
+        }
+    }
+}
+
+/// The status of one address of a batch.
 #[derive(Debug, Serialize)]
-#[serde_as]
-#[serde(remote = "ServerPlayer")]
-pub struct ServerPlayerDef {
-    /// The player's in-game name.
-    pub name: String,
+pub struct BatchEntry {
+    pub address: String,
 
-    /// The player's UUID.
-    pub id: String,
+    #[serde(flatten)]
+    pub response: Response,
 }
 
-/// Contains information about the currently online
-/// players.
-#[serde_as]
-#[serde(remote = "ServerPlayers")]
 #[derive(Debug, Serialize)]
-pub struct ServerPlayersDef {
-    /// The configured maximum number of players for the
-    /// server.
-    pub max: u32,
+pub struct PurgeResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
 
-    /// The number of players currently online.
-    pub online: u32,
+    /// The number of removed statuses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed: Option<usize>,
+}
 
-    /// An optional list of player information for
-    /// currently online players.
-    #[serde_as(as = "Option<Vec<ServerPlayerDef>>")]
+#[derive(Debug, Serialize)]
+pub struct BedrockResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sample: Option<Vec<ServerPlayer>>,
+    pub err: Option<StatusError>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<BedrockStatus>,
 }
 
-impl SerializeAs<ServerPlayer> for ServerPlayerDef {
-    fn serialize_as<S>(source: &ServerPlayer, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-        ServerPlayerDef::serialize(source, serializer)
-    }
+#[derive(Debug, Serialize)]
+pub struct QueryResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<QueryStatus>,
 }
 
-/// Contains the server's MOTD.
 #[derive(Debug, Serialize)]
-#[serde(untagged)]
-#[serde(remote = "ServerDescription")]
-pub enum ServerDescriptionDef {
-    Plain(String),
-    Object { text: String },
+pub struct VersionsResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub versions: Option<Vec<VersionProbe>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<Target>,
 }
 
-/// The decoded JSON response from a status query over
-/// ServerListPing.
+/// The players of a server, or why it couldn't be pinged.
 #[derive(Debug, Serialize)]
-#[serde(remote = "StatusResponse")]
-pub struct StatusResponseDef {
-    /// Information about the server's version.
-    #[serde(with = "ServerVersionDef")]
-    pub version: ServerVersion,
+pub struct PlayersResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
 
-    /// Information about currently online players.
-    #[serde(with = "ServerPlayersDef")]
-    pub players: ServerPlayers,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub players: Option<ServerPlayers>,
+}
 
-    /// Single-field struct containing the server's MOTD.
-    #[serde(with = "ServerDescriptionDef")]
-    pub description: ServerDescription,
+/// The version of a server, or why it couldn't be pinged.
+#[derive(Debug, Serialize)]
+pub struct VersionResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
+
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub version: Option<ServerVersion>,
+}
 
-    /// Optional field containing a path to the server's
-    /// favicon.
+/// The uptime of a monitored server, or why it couldn't be
+/// computed.
+#[derive(Debug, Serialize)]
+pub struct UptimeResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub favicon: Option<String>,
+    pub err: Option<StatusError>,
+
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub uptime: Option<Uptime>,
 }
 
-#[serde_as]
+/// The player counts of a monitored server over time, or why
+/// they couldn't be read.
 #[derive(Debug, Serialize)]
-pub struct Response {
+pub struct PlayersHistoryResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub err: Option<StatusError>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde_as(as = "Option<StatusResponseDef>")]
-    pub result: Option<StatusResponse>,
+    pub points: Option<Vec<PlayersPoint>>,
 }
 
-impl SerializeAs<StatusResponse> for StatusResponseDef {
-    fn serialize_as<S>(source: &StatusResponse, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-        StatusResponseDef::serialize(source, serializer)
-    }
+/// A page of the incidents of a monitored server, or why
+/// they couldn't be read.
+#[derive(Debug, Serialize)]
+pub struct IncidentsResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incidents: Option<Vec<Incident>>,
+
+    /// The number of the page, starting at 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_page: Option<u64>,
+
+    /// The number of incidents on all pages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
 }
 
-#[derive(Error, Debug, Serialize)]
-pub enum StatusError {
-    #[error("Protocol error")]
-    ProtocolError,
+/// The monitored servers, or why they couldn't be changed.
+#[derive(Debug, Serialize)]
+pub struct ServersResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
 
-    #[error("Invalid input string")]
-    InvalidInput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub servers: Option<Vec<servers::Entry>>,
+}
 
-    #[error("Timed out")]
-    Timeout,
+/// A page of the player sessions of a monitored server, or
+/// why they couldn't be read.
+#[derive(Debug, Serialize)]
+pub struct SessionsResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<Vec<Session>>,
+
+    /// The number of the page, starting at 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_page: Option<u64>,
+
+    /// The number of sessions on all pages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
 }
 
-#[get("/<address>")]
-async fn status(address: &str) -> (Status, &'static str) {
-    let mut split = address.split(":");
-    let result: Result<StatusResponse, StatusError> = async {
-        let host = split.next().ok_or(StatusError::InvalidInput)?;
-        let port = split.next().and_then(|x| x.parse::<u16>().ok()).unwrap_or(25565);
-        ping_timeout_retry(host, port, Duration::from_secs(3), 3).await
-    }.await;
+/// The peak player counts of a monitored server, or why they
+/// couldn't be read.
+#[derive(Debug, Serialize)]
+pub struct PeaksResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peaks: Option<Peaks>,
+}
+
+/// The imported servers and the ones that couldn't be
+/// imported, or why none could.
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub servers: Option<Vec<servers::Entry>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<Vec<servers::SkippedServer>>,
+}
+
+/// The summary of the monitored servers with a tag, or why
+/// it couldn't be read.
+#[derive(Debug, Serialize)]
+pub struct GroupResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<GroupStatus>,
+}
+
+/// The edition a server answered the ping of.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edition {
+    Java,
+    Bedrock,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum AnyStatus {
+    Java(Box<StatusResponse>),
+    Bedrock(BedrockStatus),
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnyResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edition: Option<Edition>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<AnyStatus>,
+}
+
+/// Query parameters tuning the ping of the Java Edition
+/// routes.
+#[derive(Debug, Default, FromForm)]
+struct PingQuery {
+    /// The protocol version to send in the handshake.
+    protocol: Option<i32>,
+
+    /// The server name to send in the handshake instead of the
+    /// address.
+    #[field(validate = with(|x| x.as_ref().map_or(true, |x: &String| (1..=255).contains(&x.len())), "invalid server name"))]
+    vhost: Option<String>,
+
+    /// The port to connect to, instead of one in the address.
+    port: Option<u16>,
+
+    /// The name of a configured proxy to connect through.
+    proxy: Option<String>,
+
+    /// The connect and read timeout in milliseconds, up to the
+    /// configured maximum.
+    timeout_ms: Option<u64>,
+
+    /// Whether to ping even if there is a cached status.
+    fresh: Option<bool>,
+}
+
+impl PingQuery {
+    fn options(&self, config: &Config) -> Result<PingOptions, StatusError> {
+        let mut options = config.ping_options();
+        if let Some(protocol) = self.protocol {
+            options.protocol = protocol;
+        }
+        options.vhost = self.vhost.clone();
+        if let Some(timeout) = self.timeout_ms {
+            let timeout = Duration::from_millis(timeout.min(config.timeouts.max_ms));
+            options.connect_timeout = timeout;
+            options.read_timeout = timeout;
+        }
+        if let Some(name) = &self.proxy {
+            options.proxy = Some(config.proxies.get(name).cloned().ok_or(StatusError::InvalidInput)?);
+        }
+        Ok(options)
+    }
+}
+
+#[get("/<address>?<query..>")]
+async fn status(address: &str, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> WithHeaders<(Status, &'static str)> {
+    let (target, result) = check(address, &query, config, checker).await;
 
     match result {
-        Ok(response) => {
-            (Status::Ok, "Online")
+        Ok(checked) => {
+            WithHeaders::new((Status::Ok, "Online")).checked(Some(&checked))
+        }
+        Err(StatusError::Forbidden) => {
+            WithHeaders::new((Status::Forbidden, "Forbidden"))
+        }
+        Err(_) => {
+            WithHeaders::new((Status::ServiceUnavailable, "Offline")).ping(None).header(retry_after(target.as_ref(), config, checker))
+        }
+    }
+}
+
+/// `Retry-After` for a server that couldn't be pinged, the
+/// time until it may be pinged again, otherwise the TTL of
+/// the cache, but at least a second.
+fn retry_after(target: Option<&Target>, config: &Config, checker: &Checker) -> Header<'static> {
+    let wait = target.and_then(|x| checker.targets.quota(&(x.connect_host(), x.port)))
+        .map_or(Duration::from_secs(config.cache.ttl), |x| x.reset);
+    RetryAfter::header(wait)
+}
+
+/// Responds the JSON status, with only the comma separated
+/// `fields` of the `result` if given, i.e.
+/// `?fields=players.online,version.name`.
+#[get("/<address>/json?<fields>&<query..>")]
+async fn status_json(address: &str, fields: Option<&str>, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> WithHeaders<(Status, Either<Formatted<Response>, Formatted<Value>>)> {
+    let (target, result) = check(address, &query, config, checker).await;
+
+    let mut headers = headers::checked_headers(result.as_ref().ok());
+    if result.is_ok() {
+        headers.push(cache_control(config, &config.cache_control.json));
+    }
+    let (status, response) = match result {
+        Ok(checked) => {
+            (Status::Ok, Response::from_checked(checked, target))
+        }
+        Err(StatusError::Forbidden) => {
+            (Status::Forbidden, Response::error(StatusError::Forbidden, target))
+        }
+        Err(e) => {
+            (Status::Ok, Response::error(e, target))
+        }
+    };
+
+    // Errors have no fields to select and are serialized by
+    // the version of the route
+    let body = match fields {
+        Some(fields) if response.err.is_none() => {
+            let mut value = serde_json::to_value(&response).unwrap_or_default();
+            if let Some(result) = value.get_mut("result") {
+                *result = fields::select(result, fields);
+            }
+            Either::Right(Formatted(value))
+        }
+        _ => Either::Left(Formatted(response)),
+    };
+    WithHeaders::new((status, body)).headers(headers)
+}
+
+/// Pings all addresses of the JSON array in the body, a
+/// limited number at once, and responds their statuses in
+/// the same order.
+#[post("/batch?<query..>", data = "<addresses>")]
+async fn batch(addresses: Json<Vec<String>>, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> Result<Formatted<Vec<BatchEntry>>, (Status, Formatted<Response>)> {
+    if addresses.len() > config.batch.max_size {
+        return Err((Status::BadRequest, Formatted(Response::error(StatusError::InvalidInput, None))));
+    }
+
+    let entries = stream::iter(addresses.into_inner())
+        .map(|address| async {
+            let (target, result) = check(&address, &query, config, checker).await;
+            let response = match result {
+                Ok(checked) => Response::from_checked(checked, target),
+                Err(e) => Response::error(e, target),
+            };
+            BatchEntry { address, response }
+        })
+        .buffered(config.batch.concurrency.max(1))
+        .collect()
+        .await;
+
+    Ok(Formatted(entries))
+}
+
+/// Like `batch`, but streams every status as a line of JSON
+/// as soon as its ping completes.
+#[post("/batch/stream?<query..>", data = "<addresses>")]
+async fn batch_stream<'r>(addresses: Json<Vec<String>>, query: PingQuery, uri: &Origin<'_>, _limit: ClientLimit, config: &'r State<Config>, checker: &'r State<Checker>) -> Result<(ContentType, TextStream<impl Stream<Item = String> + Send + 'r>), (Status, Formatted<Response>)> {
+    if addresses.len() > config.batch.max_size {
+        return Err((Status::BadRequest, Formatted(Response::error(StatusError::InvalidInput, None))));
+    }
+
+    let query = Arc::new(query);
+    let v2 = is_v2(uri);
+    let lines = stream::iter(addresses.into_inner())
+        .map(move |address| {
+            let query = query.clone();
+            async move {
+                let (target, result) = check(&address, &query, config, checker).await;
+                let response = match result {
+                    Ok(checked) => Response::from_checked(checked, target),
+                    Err(e) => Response::error(e, target),
+                };
+                let entry = BatchEntry { address, response };
+                let line = match v2 {
+                    true => serde_json::to_string(&entry),
+                    false => serde_json::to_string(&V1(&entry)),
+                };
+                let mut line = line.unwrap_or_default();
+                line.push('\n');
+                line
+            }
+        })
+        .buffer_unordered(config.batch.concurrency.max(1));
+
+    Ok((ContentType::new("application", "x-ndjson"), TextStream(lines)))
+}
+
+/// Removes the cached statuses of `address`, so the next
+/// request pings it again.
+#[delete("/cache/<address>?<port>")]
+fn purge_cache(address: &str, port: Option<u16>, _admin: Admin, checker: &State<Checker>) -> (Status, Formatted<PurgeResponse>) {
+    match parse_address(address, port) {
+        Ok((host, port)) => {
+            let removed = checker.cache.remove(&cache::normalize_host(host), port);
+            (Status::Ok, Formatted(PurgeResponse { err: None, removed: Some(removed) }))
         }
         Err(e) => {
-            (Status::ServiceUnavailable, "Offline")
+            (Status::BadRequest, Formatted(PurgeResponse { err: Some(e.into()), removed: None }))
         }
     }
 }
 
-#[get("/<address>/json")]
-async fn status_json(address: &str) -> Json<Response> {
-    let mut split = address.split(":");
-    let result: Result<StatusResponse, StatusError> = async {
-        let host = split.next().ok_or(StatusError::InvalidInput)?;
-        let port = split.next().and_then(|x| x.parse::<u16>().ok()).unwrap_or(25565);
-        ping_timeout_retry(host, port, Duration::from_secs(3), 3).await
+/// Removes all cached statuses.
+#[delete("/cache")]
+fn clear_cache(_admin: Admin, checker: &State<Checker>) -> Formatted<PurgeResponse> {
+    Formatted(PurgeResponse { err: None, removed: Some(checker.cache.clear()) })
+}
+
+#[get("/<address>/bedrock?<port>")]
+async fn status_bedrock(address: &str, port: Option<u16>, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> Formatted<BedrockResponse> {
+    let result: Result<BedrockStatus, StatusError> = async {
+        let (host, port) = parse_address(address, port)?;
+        let target = resolve_allowed(checker, host, port.unwrap_or(19132)).await?;
+        Ok(bedrock::ping_bedrock(&target.connect_host(), target.port, config.timeouts.udp(), config.timeouts.attempts.max(1)).await?)
     }.await;
 
-    Json(match result {
+    Formatted(match result {
         Ok(response) => {
-            Response {
+            BedrockResponse {
                 result: Some(response),
-                err: None
+                err: None,
+            }
+        }
+        Err(e) => {
+            BedrockResponse {
+                result: None,
+                err: Some(e),
+            }
+        }
+    })
+}
+
+#[get("/<address>/query?<basic>&<port>")]
+async fn status_query(address: &str, basic: Option<bool>, port: Option<u16>, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> Formatted<QueryResponse> {
+    let result: Result<QueryStatus, StatusError> = async {
+        let (host, port) = parse_address(address, port)?;
+        let target = resolve_allowed(checker, host, port.unwrap_or(25565)).await?;
+        Ok(query::query(&target.connect_host(), target.port, basic.unwrap_or(false), config.timeouts.udp(), config.timeouts.attempts.max(1)).await?)
+    }.await;
+
+    Formatted(match result {
+        Ok(response) => {
+            QueryResponse {
+                result: Some(response),
+                err: None,
+            }
+        }
+        Err(e) => {
+            QueryResponse {
+                result: None,
+                err: Some(e),
+            }
+        }
+    })
+}
+
+/// The formats the MOTD can be rendered in.
+#[derive(Debug, Clone, Copy, FromFormField)]
+enum MotdFormat {
+    /// The chat component JSON as sent by the server.
+    Raw,
+    /// Plain text without formatting.
+    Clean,
+    /// HTML with `mc-*` classes.
+    Html,
+    /// Text with ANSI escape sequences.
+    Ansi,
+}
+
+#[get("/<address>/motd?<format>&<query..>")]
+async fn motd(address: &str, format: Option<MotdFormat>, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> WithHeaders<(Status, (ContentType, String))> {
+    let (target, result) = check(address, &query, config, checker).await;
+
+    let mut headers = headers::checked_headers(result.as_ref().ok());
+    if result.is_err() {
+        headers.push(retry_after(target.as_ref(), config, checker));
+    }
+    let response = match result {
+        Ok(Checked { ping, .. }) => {
+            let description = &ping.status.description;
+            let body = match format.unwrap_or(MotdFormat::Clean) {
+                MotdFormat::Raw => (ContentType::JSON, serde_json::to_string(description).unwrap_or_default()),
+                MotdFormat::Clean => (ContentType::Plain, ping.status.motd),
+                MotdFormat::Html => (ContentType::HTML, description.to_html()),
+                MotdFormat::Ansi => (ContentType::Plain, description.to_ansi()),
+            };
+            (Status::Ok, body)
+        }
+        Err(_) => {
+            (Status::ServiceUnavailable, (ContentType::Plain, "Offline".to_string()))
+        }
+    };
+    WithHeaders::new(response).headers(headers)
+}
+
+/// Responds only the online and maximum player count and
+/// the player sample.
+#[get("/<address>/players?<query..>")]
+async fn status_players(address: &str, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> WithHeaders<Formatted<PlayersResponse>> {
+    let (_, result) = check(address, &query, config, checker).await;
+
+    let headers = headers::checked_headers(result.as_ref().ok());
+    let response = Formatted(match result {
+        Ok(checked) => {
+            PlayersResponse {
+                err: None,
+                players: Some(checked.ping.status.players),
+            }
+        }
+        Err(e) => {
+            PlayersResponse {
+                err: Some(e),
+                players: None,
+            }
+        }
+    });
+    WithHeaders::new(response).headers(headers)
+}
+
+/// Responds only the version name and protocol number.
+#[get("/<address>/version?<query..>")]
+async fn status_version(address: &str, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> WithHeaders<Formatted<VersionResponse>> {
+    let (_, result) = check(address, &query, config, checker).await;
+
+    let headers = headers::checked_headers(result.as_ref().ok());
+    let response = Formatted(match result {
+        Ok(checked) => {
+            VersionResponse {
+                err: None,
+                version: Some(checked.ping.status.version),
+            }
+        }
+        Err(e) => {
+            VersionResponse {
+                err: Some(e),
+                version: None,
+            }
+        }
+    });
+    WithHeaders::new(response).headers(headers)
+}
+
+/// Responds a badge like "minecraft | 42/100 online", with
+/// the `label` on the left if given.
+#[get("/<address>/badge.svg?<label>&<query..>")]
+async fn status_badge(address: &str, label: Option<&str>, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> WithHeaders<(ContentType, String)> {
+    let (_, result) = check(address, &query, config, checker).await;
+
+    let headers = headers::checked_headers(result.as_ref().ok());
+    let label = label.unwrap_or("minecraft");
+    let svg = match result {
+        Ok(checked) => {
+            let players = &checked.ping.status.players;
+            badge::render(label, &format!("{}/{} online", players.online, players.max), badge::ONLINE)
+        }
+        Err(_) => {
+            badge::render(label, "offline", badge::OFFLINE)
+        }
+    };
+    WithHeaders::new((ContentType::SVG, svg)).header(cache_control(config, &config.cache_control.badge)).headers(headers)
+}
+
+/// Responds a PNG banner like the entry of the server in the
+/// server list, with `name` instead of the address if given.
+#[get("/<address>/banner.png?<name>&<query..>")]
+async fn status_banner(address: &str, name: Option<&str>, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>, renderer: &State<Option<BannerRenderer>>) -> Result<WithHeaders<(ContentType, Vec<u8>)>, Status> {
+    let renderer = renderer.as_ref().ok_or(Status::ServiceUnavailable)?;
+    let (_, result) = check(address, &query, config, checker).await;
+
+    let png = renderer.render(name.unwrap_or(address), result.as_ref().ok().map(|x| &x.ping));
+    Ok(WithHeaders::new((ContentType::PNG, png)).header(cache_control(config, &config.cache_control.banner)).checked(result.as_ref().ok()))
+}
+
+/// Responds the icon of the server as PNG, 404 Not Found if
+/// it has no valid one.
+#[get("/<address>/icon.png?<query..>")]
+async fn status_icon(address: &str, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> Result<WithHeaders<(ContentType, Vec<u8>)>, Status> {
+    let (_, result) = check(address, &query, config, checker).await;
+
+    let checked = result.map_err(|_| Status::ServiceUnavailable)?;
+    let png = checked.ping.status.favicon.as_deref().and_then(favicon::decode_png).ok_or(Status::NotFound)?;
+    Ok(WithHeaders::new((ContentType::PNG, png)).header(cache_control(config, &config.cache_control.icon)).checked(Some(&checked)))
+}
+
+/// The `configured` `Cache-Control` of a route, otherwise
+/// letting clients cache a status for as long as it is
+/// cached itself.
+fn cache_control(config: &Config, configured: &str) -> Header<'static> {
+    let value = match (configured, config.cache.ttl) {
+        ("", 0) => "no-cache".to_string(),
+        ("", ttl) => format!("public, max-age={}", ttl),
+        (value, _) => value.to_string(),
+    };
+    Header::new("Cache-Control", value)
+}
+
+/// Responds the MOTD as plain text, the same as
+/// `motd?format=clean`.
+#[get("/<address>/motd.txt?<query..>")]
+async fn motd_text(address: &str, query: PingQuery, limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> WithHeaders<(Status, (ContentType, String))> {
+    motd(address, Some(MotdFormat::Clean), query, limit, config, checker).await
+}
+
+/// Responds the availability of a monitored server over the
+/// last 24 hours, 7 and 30 days, with its longest outage and
+/// current streak.
+#[get("/<address>/uptime?<port>")]
+async fn status_uptime(address: &str, port: Option<u16>, _limit: ClientLimit, servers: &State<Arc<MonitoredServers>>, history: &State<Option<History>>) -> (Status, Formatted<UptimeResponse>) {
+    let result: Result<Uptime, StatusError> = async {
+        let (server, history) = monitored(servers, history, address, port)?;
+        let now = SystemTime::now();
+        let since = now - Duration::from_secs(uptime::MAX_WINDOW);
+        let checks = history.checks(&server, since).await.map_err(|e| history_error(&server, &e))?;
+        Ok(uptime::compute(&checks, unix_time(now)))
+    }.await;
+
+    match result {
+        Ok(uptime) => (Status::Ok, Formatted(UptimeResponse { err: None, uptime: Some(uptime) })),
+        Err(e) => (history_status(&e), Formatted(UptimeResponse { err: Some(e), uptime: None })),
+    }
+}
+
+/// Responds the most players a monitored server had online
+/// at once today, in the last 7 days and ever.
+#[get("/<address>/peaks?<port>")]
+async fn status_peaks(address: &str, port: Option<u16>, _limit: ClientLimit, servers: &State<Arc<MonitoredServers>>, history: &State<Option<History>>) -> (Status, Formatted<PeaksResponse>) {
+    let result: Result<Peaks, StatusError> = async {
+        let (server, history) = monitored(servers, history, address, port)?;
+        history.peaks(&server, SystemTime::now()).await.map_err(|e| history_error(&server, &e))
+    }.await;
+
+    match result {
+        Ok(peaks) => (Status::Ok, Formatted(PeaksResponse { err: None, peaks: Some(peaks) })),
+        Err(e) => (history_status(&e), Formatted(PeaksResponse { err: Some(e), peaks: None })),
+    }
+}
+
+/// Query parameters selecting a time range of the history.
+#[derive(Debug, FromForm)]
+struct RangeQuery {
+    /// The start in seconds since the Unix epoch.
+    from: Option<u64>,
+
+    /// The end in seconds since the Unix epoch.
+    to: Option<u64>,
+
+    /// The seconds to aggregate into one point.
+    step: Option<u64>,
+
+    /// The port of the server, instead of one in the address.
+    port: Option<u16>,
+}
+
+/// The most points of a time series responded at once.
+const MAX_POINTS: u64 = 10000;
+
+/// Responds the player counts of a monitored server between
+/// `from` and `to` in seconds since the Unix epoch, the last
+/// 24 hours by default, averaged over every `step` seconds,
+/// the monitoring interval by default.
+#[get("/<address>/history/players?<range..>")]
+async fn history_players(address: &str, range: RangeQuery, _limit: ClientLimit, config: &State<Config>, servers: &State<Arc<MonitoredServers>>, history: &State<Option<History>>) -> (Status, Formatted<PlayersHistoryResponse>) {
+    let result: Result<Vec<PlayersPoint>, StatusError> = async {
+        let (server, history) = monitored(servers, history, address, range.port)?;
+        let to = range.to.unwrap_or_else(|| unix_time(SystemTime::now()));
+        let from = range.from.unwrap_or(to.saturating_sub(24 * 60 * 60));
+        let step = range.step.unwrap_or(config.monitor.interval).max(1);
+        if from > to || (to - from) / step > MAX_POINTS {
+            return Err(StatusError::InvalidInput);
+        }
+        history.players(&server, from, to, step).await.map_err(|e| history_error(&server, &e))
+    }.await;
+
+    match result {
+        Ok(points) => (Status::Ok, Formatted(PlayersHistoryResponse { err: None, points: Some(points) })),
+        Err(e) => (history_status(&e), Formatted(PlayersHistoryResponse { err: Some(e), points: None })),
+    }
+}
+
+/// Streams the stored checks of a monitored server between
+/// `from` and `to` in seconds since the Unix epoch as CSV,
+/// all of them by default.
+#[get("/<address>/history.csv?<range..>")]
+async fn history_csv<'r>(address: &str, range: RangeQuery, _limit: ClientLimit, servers: &State<Arc<MonitoredServers>>, history: &'r State<Option<History>>) -> Result<(ContentType, TextStream<impl Stream<Item = String> + Send + 'r>), (Status, Formatted<Response>)> {
+    let (server, history) = monitored(servers, history.inner(), address, range.port)
+        .map_err(|e| (history_status(&e), Formatted(Response::error(e, None))))?;
+    let from = range.from.unwrap_or(0);
+    let to = range.to.unwrap_or(i64::MAX as u64);
+    if from > to {
+        return Err((Status::BadRequest, Formatted(Response::error(StatusError::InvalidInput, None))));
+    }
+
+    let records = history.records(server.clone(), from, to)
+        .take_while(move |x| {
+            if let Err(e) = x {
+                history_error(&server, e);
             }
+            future::ready(x.is_ok())
+        })
+        .filter_map(|x| future::ready(x.ok()))
+        .map(|x| x.to_csv());
+    let lines = stream::once(future::ready(history::CSV_HEADER.to_string())).chain(records);
+    Ok((ContentType::CSV, TextStream(lines)))
+}
+
+/// The most incidents on one page.
+const MAX_PER_PAGE: u64 = 100;
+
+/// Responds the incidents of a monitored server, newest first,
+/// `per_page` of them on every page.
+#[get("/<address>/incidents?<page>&<per_page>&<port>")]
+async fn incidents(address: &str, page: Option<u64>, per_page: Option<u64>, port: Option<u16>, _limit: ClientLimit, servers: &State<Arc<MonitoredServers>>, history: &State<Option<History>>) -> (Status, Formatted<IncidentsResponse>) {
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page.unwrap_or(20).clamp(1, MAX_PER_PAGE);
+    let result: Result<(Vec<Incident>, u64), StatusError> = async {
+        let (server, history) = monitored(servers, history, address, port)?;
+        history.incidents(&server, (page - 1).saturating_mul(per_page), per_page).await.map_err(|e| history_error(&server, &e))
+    }.await;
+
+    match result {
+        Ok((incidents, total)) => {
+            (Status::Ok, Formatted(IncidentsResponse { err: None, incidents: Some(incidents), page: Some(page), per_page: Some(per_page), total: Some(total) }))
         }
         Err(e) => {
-            Response {
+            (history_status(&e), Formatted(IncidentsResponse { err: Some(e), incidents: None, page: None, per_page: None, total: None }))
+        }
+    }
+}
+
+/// Query parameters selecting a page.
+#[derive(Debug, FromForm)]
+struct PageQuery {
+    /// The number of the page, starting at 1.
+    page: Option<u64>,
+
+    /// The number of items on every page.
+    per_page: Option<u64>,
+
+    /// The port of the server, instead of one in the address.
+    port: Option<u16>,
+}
+
+/// Responds the sessions of the players of a monitored server
+/// inferred from the samples of its checks, newest first,
+/// optionally only of the player with the name or UUID.
+#[get("/<address>/sessions?<player>&<paging..>")]
+async fn sessions(address: &str, player: Option<&str>, paging: PageQuery, _limit: ClientLimit, config: &State<Config>, servers: &State<Arc<MonitoredServers>>, history: &State<Option<History>>) -> (Status, Formatted<SessionsResponse>) {
+    let page = paging.page.unwrap_or(1).max(1);
+    let per_page = paging.per_page.unwrap_or(20).clamp(1, MAX_PER_PAGE);
+    let result: Result<(Vec<Session>, u64), StatusError> = async {
+        let (server, history) = monitored(servers, history, address, paging.port)?;
+        let timeout = Duration::from_secs(config.history.session_timeout_minutes * 60);
+        history.sessions(&server, player, (page - 1).saturating_mul(per_page), per_page, SystemTime::now(), timeout).await
+            .map_err(|e| history_error(&server, &e))
+    }.await;
+
+    match result {
+        Ok((sessions, total)) => {
+            (Status::Ok, Formatted(SessionsResponse { err: None, sessions: Some(sessions), page: Some(page), per_page: Some(per_page), total: Some(total) }))
+        }
+        Err(e) => {
+            (history_status(&e), Formatted(SessionsResponse { err: Some(e), sessions: None, page: None, per_page: None, total: None }))
+        }
+    }
+}
+
+/// The most incidents in a feed.
+const FEED_INCIDENTS: u64 = 50;
+
+/// Responds an Atom feed of the monitored servers going
+/// offline and online again.
+#[get("/feed.atom")]
+async fn incidents_feed(_limit: ClientLimit, history: &State<Option<History>>) -> Result<(ContentType, String), Status> {
+    let history = history.as_ref().ok_or(Status::NotFound)?;
+    let incidents = history.latest_incidents(None, FEED_INCIDENTS).await
+        .map_err(|e| history_status(&history_error("all servers", &e)))?;
+    let feed = feed::render("Minecraft server status", "urn:mcstatus-checker:feed", &incidents, unix_time(SystemTime::now()));
+    Ok((feed::content_type(), feed))
+}
+
+/// Responds an Atom feed of a monitored server going offline
+/// and online again.
+#[get("/<address>/feed.atom?<port>")]
+async fn server_feed(address: &str, port: Option<u16>, _limit: ClientLimit, servers: &State<Arc<MonitoredServers>>, history: &State<Option<History>>) -> Result<(ContentType, String), Status> {
+    let (server, history) = monitored(servers, history, address, port).map_err(|e| history_status(&e))?;
+    let incidents = history.latest_incidents(Some(&server), FEED_INCIDENTS).await
+        .map_err(|e| history_status(&history_error(&server, &e)))?;
+    let title = format!("Status of {}", server);
+    let id = format!("urn:mcstatus-checker:feed:{}", server);
+    Ok((feed::content_type(), feed::render(&title, &id, &incidents, unix_time(SystemTime::now()))))
+}
+
+/// Responds whether the monitored servers with `tag` are all
+/// online, some or none of them as of their latest checks,
+/// with their players summed up.
+#[get("/groups/<tag>/status")]
+async fn group_status(tag: &str, _limit: ClientLimit, servers: &State<Arc<MonitoredServers>>, history: &State<Option<History>>) -> (Status, Formatted<GroupResponse>) {
+    let result: Result<GroupStatus, StatusError> = async {
+        let tagged = servers.tagged(tag);
+        let history = history.as_ref().filter(|_| !tagged.is_empty()).ok_or(StatusError::UnknownTag)?;
+        let latest = future::try_join_all(tagged.into_iter().map(|server| async move {
+            let latest = history.latest(&server.address).await.map_err(|e| history_error(&server.address, &e))?;
+            Ok::<_, StatusError>(GroupServer::new(server.address, latest.as_ref()))
+        })).await?;
+        Ok(groups::summarize(tag, latest))
+    }.await;
+
+    match result {
+        Ok(group) => (Status::Ok, Formatted(GroupResponse { err: None, group: Some(group) })),
+        Err(e) => (history_status(&e), Formatted(GroupResponse { err: Some(e), group: None })),
+    }
+}
+
+/// Logs a failure to read the history of `server`.
+fn history_error(server: &str, e: &sqlx::Error) -> StatusError {
+    error!("can't read the history of {}: {}", server, e);
+    report::storage_error(Some(server), e);
+    StatusError::HistoryError
+}
+
+/// The HTTP status of the history routes for `e`.
+fn history_status(e: &StatusError) -> Status {
+    match e {
+        StatusError::NotMonitored | StatusError::UnknownTag => Status::NotFound,
+        StatusError::HistoryError => Status::InternalServerError,
+        StatusError::AlreadyMonitored | StatusError::Configured => Status::Conflict,
+        _ => Status::BadRequest,
+    }
+}
+
+/// Seconds since the Unix epoch.
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs())
+}
+
+/// Responds all monitored servers and where they come from.
+#[get("/admin/servers")]
+fn list_servers(_admin: Admin, servers: &State<Arc<MonitoredServers>>) -> Formatted<ServersResponse> {
+    Formatted(ServersResponse { err: None, servers: Some(servers.list()) })
+}
+
+/// Responds the changed monitored server, or why it couldn't
+/// be changed.
+fn server_changed(result: Result<servers::Entry, StatusError>, success: Status) -> (Status, Formatted<ServersResponse>) {
+    match result {
+        Ok(entry) => (success, Formatted(ServersResponse { err: None, servers: Some(vec![entry]) })),
+        Err(e) => (history_status(&e), Formatted(ServersResponse { err: Some(e), servers: None })),
+    }
+}
+
+/// Starts monitoring a server, configured like in
+/// `monitor.servers`, until it is removed again.
+#[post("/admin/servers", data = "<server>")]
+async fn add_server(server: Json<MonitoredServer>, _admin: Admin, servers: &State<Arc<MonitoredServers>>) -> (Status, Formatted<ServersResponse>) {
+    server_changed(servers.add(server.into_inner()).await, Status::Created)
+}
+
+/// Replaces the settings of a monitored server added at
+/// runtime.
+#[put("/admin/servers/<address>", data = "<server>")]
+async fn update_server(address: &str, server: Json<MonitoredServer>, _admin: Admin, servers: &State<Arc<MonitoredServers>>) -> (Status, Formatted<ServersResponse>) {
+    server_changed(servers.update(address, server.into_inner()).await, Status::Ok)
+}
+
+/// Stops monitoring a server added at runtime, keeping its
+/// history.
+#[delete("/admin/servers/<address>")]
+async fn remove_server(address: &str, _admin: Admin, servers: &State<Arc<MonitoredServers>>) -> (Status, Formatted<ServersResponse>) {
+    server_changed(servers.remove(address).await, Status::Ok)
+}
+
+/// The most bytes of servers imported at once.
+const IMPORT_LIMIT: ByteUnit = ByteUnit::Mebibyte(1);
+
+/// Adds the servers of a JSON array like `monitor.servers`,
+/// or of CSV if sent as `text/csv`, replacing the settings of
+/// the ones added at runtime before.
+#[post("/admin/servers/import", data = "<data>")]
+async fn import_servers(content_type: Option<&ContentType>, data: Data<'_>, _admin: Admin, servers: &State<Arc<MonitoredServers>>) -> (Status, Formatted<ImportResponse>) {
+    let refuse = |status, e| (status, Formatted(ImportResponse { err: Some(e), servers: None, skipped: None }));
+    let body = match data.open(IMPORT_LIMIT).into_string().await {
+        Ok(body) if body.is_complete() => body.into_inner(),
+        Ok(_) => return refuse(Status::PayloadTooLarge, StatusError::InvalidInput),
+        Err(_) => return refuse(Status::BadRequest, StatusError::InvalidInput),
+    };
+    let parsed = if content_type.is_some_and(|x| x.sub() == "csv") {
+        servers::from_csv(&body).ok()
+    } else {
+        serde_json::from_str::<Vec<MonitoredServer>>(&body).ok()
+    };
+    let Some(parsed) = parsed else {
+        return refuse(Status::BadRequest, StatusError::InvalidInput);
+    };
+
+    let (imported, skipped) = servers.import(parsed).await;
+    (Status::Ok, Formatted(ImportResponse { err: None, servers: Some(imported), skipped: Some(skipped) }))
+}
+
+/// The monitored servers as JSON or CSV.
+type Export = Either<Json<Vec<MonitoredServer>>, (ContentType, String)>;
+
+/// Responds all monitored servers as JSON array to import
+/// elsewhere, or as CSV with `?format=csv`.
+#[get("/admin/servers/export?<format>")]
+fn export_servers(format: Option<&str>, _admin: Admin, servers: &State<Arc<MonitoredServers>>) -> Result<Export, Status> {
+    let list = servers.servers();
+    match format {
+        None | Some("json") => Ok(Either::Left(Json(list))),
+        Some("csv") => {
+            let csv = servers::to_csv(&list).map_err(|e| {
+                error!("can't export the monitored servers: {}", e);
+                Status::InternalServerError
+            })?;
+            Ok(Either::Right((ContentType::CSV, csv)))
+        }
+        Some(_) => Err(Status::BadRequest),
+    }
+}
+
+/// Reads the configuration again and applies the monitored
+/// servers, the settings of the monitor and the rate limits.
+/// Other settings take effect after a restart.
+#[post("/admin/reload")]
+async fn reload(_admin: Admin, servers: &State<Arc<MonitoredServers>>, settings: &State<Arc<MonitorSettings>>, client_limiter: &State<RateLimiter<IpAddr>>, checker: &State<Checker>) -> (Status, Formatted<ServersResponse>) {
+    let config: Config = match figment().extract() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("can't reload the configuration: {}", e);
+            return (Status::BadRequest, Formatted(ServersResponse { err: Some(StatusError::InvalidConfig), servers: None }));
+        }
+    };
+
+    servers.reload(&config.monitor).await;
+    settings.set(config.monitor);
+    client_limiter.set_limits(config.rate_limit.per_minute, config.rate_limit.burst);
+    checker.targets.set_interval(Duration::from_secs(config.rate_limit.target_interval));
+    (Status::Ok, Formatted(ServersResponse { err: None, servers: Some(servers.list()) }))
+}
+
+/// Races a ServerListPing against a Bedrock ping and responds
+/// whichever succeeds first.
+#[get("/<address>/any?<query..>")]
+async fn status_any(address: &str, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> Formatted<AnyResponse> {
+    let java = async {
+        let (_, result) = check(address, &query, config, checker).await;
+        result.map(|x| (Edition::Java, AnyStatus::Java(Box::new(x.ping.status))))
+    };
+    let bedrock = async {
+        let (host, port) = parse_address(address, query.port)?;
+        let target = resolve_allowed(checker, host, port.unwrap_or(19132)).await?;
+        let status = bedrock::ping_bedrock(&target.connect_host(), target.port, config.timeouts.udp(), config.timeouts.attempts.max(1)).await?;
+        Ok((Edition::Bedrock, AnyStatus::Bedrock(status)))
+    };
+
+    let result = select_ok([java.boxed(), bedrock.boxed()]).await;
+
+    Formatted(match result {
+        Ok(((edition, status), _)) => {
+            AnyResponse {
+                err: None,
+                edition: Some(edition),
+                result: Some(status),
+            }
+        }
+        Err(e) => {
+            AnyResponse {
+                err: Some(e),
+                edition: None,
                 result: None,
-                err: Some(e)
             }
         }
     })
 }
 
-async fn ping_timeout_retry(host: &str, port: u16, timeout: Duration, retry: usize) -> Result<StatusResponse, StatusError> {
+/// The most protocol versions probed at once.
+const MAX_PROBED_PROTOCOLS: usize = 32;
+
+#[get("/<address>/versions?<protocols>&<port>")]
+async fn status_versions(address: &str, protocols: Option<&str>, port: Option<u16>, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> Formatted<VersionsResponse> {
+    let protocols: Result<Vec<i32>, StatusError> = match protocols {
+        Some(protocols) => protocols.split(',')
+            .map(|x| x.trim().parse::<i32>().map_err(|_| StatusError::InvalidInput))
+            .collect(),
+        None => Ok(versions::DEFAULT_PROTOCOLS.iter().map(|x| x.0).collect()),
+    };
+    let (host, port, protocols) = match (parse_address(address, port), protocols) {
+        (Ok((host, port)), Ok(protocols)) if protocols.len() <= MAX_PROBED_PROTOCOLS => (host, port, protocols),
+        (Err(e), _) => return Formatted(VersionsResponse { err: Some(e.into()), versions: None, target: None }),
+        (_, Err(e)) => return Formatted(VersionsResponse { err: Some(e), versions: None, target: None }),
+        _ => return Formatted(VersionsResponse { err: Some(StatusError::InvalidInput), versions: None, target: None }),
+    };
+    let target = dns::resolve(&checker.resolver, host, port).await;
+    if !checker.filter.allows(host, &target) {
+        return Formatted(VersionsResponse { err: Some(StatusError::Forbidden), versions: None, target: Some(target) });
+    }
+    let options = config.ping_options().with_server_name(&target.host);
+    // Proxies resolve the host themselves
+    if let (Some(e), None) = (target.unresolved.clone(), &options.proxy) {
+        return Formatted(VersionsResponse { err: Some(e.into()), versions: None, target: Some(target) });
+    }
+    if checker.targets.take((target.connect_host(), target.port)).is_err() {
+        return Formatted(VersionsResponse { err: Some(StatusError::RateLimited), versions: None, target: Some(target) });
+    }
+    let versions = versions::probe(&target.tcp_host(options.proxy.as_ref()), target.port, &options, &protocols).await;
+
+    Formatted(VersionsResponse {
+        err: None,
+        versions: Some(versions),
+        target: Some(target),
+    })
+}
+
+#[get("/<address>/raw?<query..>")]
+async fn raw(address: &str, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> Result<RawJson<String>, (Status, Formatted<Response>)> {
+    let (host, port) = parse_address(address, query.port).map_err(|e| (Status::BadRequest, Formatted(Response::error(e.into(), None))))?;
+    let options = query.options(config).map_err(|e| (Status::BadRequest, Formatted(Response::error(e, None))))?;
+    let target = dns::resolve(&checker.resolver, host, port).await;
+    if !checker.filter.allows(host, &target) {
+        return Err((Status::Forbidden, Formatted(Response::error(StatusError::Forbidden, Some(target)))));
+    }
+    if let (Some(e), None) = (target.unresolved.clone(), &options.proxy) {
+        return Err((Status::ServiceUnavailable, Formatted(Response::error(e.into(), Some(target)))));
+    }
+    if checker.targets.take((target.connect_host(), target.port)).is_err() {
+        return Err((Status::TooManyRequests, Formatted(Response::error(StatusError::RateLimited, Some(target)))));
+    }
+    let options = options.with_server_name(&target.host);
+
     let mut last_err = StatusError::ProtocolError;
-    for i in 0..retry {
-        match ping_timeout(host, port, timeout).await {
-            Ok(x) => {
-                return Ok(x);
+    for _ in 0..config.timeouts.attempts.max(1) {
+        match java::ping_raw(&target.tcp_host(options.proxy.as_ref()), target.port, &options).await {
+            Ok(body) => {
+                return Ok(RawJson(body));
             }
             Err(e) => {
-                last_err = e;
+                last_err = e.into();
             }
         }
     }
-    Err(last_err)
+    Err((Status::ServiceUnavailable, Formatted(Response::error(last_err, Some(target)))))
+}
+
+/// Responds the metrics in the Prometheus text format.
+#[get("/metrics")]
+fn export_metrics(metrics: &State<Arc<Metrics>>) -> (ContentType, String) {
+    (metrics::content_type(), metrics.encode())
 }
 
-async fn ping_timeout(host: &str, port: u16, timeout: Duration) -> Result<StatusResponse, StatusError> {
-    match tokio::time::timeout(timeout, ping(host, port)).await {
-        Ok(x) => {
-            match x {
-                Ok(y) => {
-                    Ok(y)
-                }
-                Err(_) => {
-                    Err(StatusError::ProtocolError)
-                }
+/// Responds the version and build of the checker and what
+/// it is busy with.
+#[get("/about")]
+fn self_status(started: &State<Started>, checker: &State<Checker>, servers: Option<&State<Arc<MonitoredServers>>>) -> Json<About> {
+    Json(About::new(started, servers.map_or(0, |x| x.list().len()), checker.cache.size(), checker.metrics.pings_in_flight()))
+}
+
+/// Responds as long as the process serves requests.
+#[get("/healthz")]
+fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Responds whether the scheduler runs, the history can be
+/// queried and DNS works, with `503 Service Unavailable` if
+/// any of them fails.
+#[get("/readyz")]
+async fn readyz(config: &State<Config>, checker: &State<Checker>, history: &State<Option<History>>, heartbeat: Option<&State<Arc<Heartbeat>>>) -> (Status, Json<Readiness>) {
+    let readiness = health::readiness(history.inner().as_ref(), heartbeat.map(|x| x.inner().as_ref()), &checker.resolver, &config.dns.health_check).await;
+    let status = if readiness.ready { Status::Ok } else { Status::ServiceUnavailable };
+    (status, Json(readiness))
+}
+
+/// Pings `target` like a module of the Prometheus blackbox
+/// exporter, responding the metrics of only this ping.
+#[get("/probe?<target>&<query..>")]
+async fn probe(target: &str, query: PingQuery, _limit: ClientLimit, config: &State<Config>, checker: &State<Checker>) -> (ContentType, String) {
+    let start = Instant::now();
+    let (_, result) = check(target, &query, config, checker).await;
+    let ping = result.as_ref().ok().map(|x| &x.ping);
+    (metrics::content_type(), metrics::encode_probe(ping, start.elapsed()))
+}
+
+/// The address of the monitored server that `address`
+/// refers to, and the history it is stored in.
+fn monitored<'a>(servers: &MonitoredServers, history: &'a Option<History>, address: &str, port: Option<u16>) -> Result<(String, &'a History), StatusError> {
+    let entry = servers.find(address, port)?;
+    Ok((entry.server.address, history.as_ref().ok_or(StatusError::NotMonitored)?))
+}
+
+/// Resolves `host` without following SRV records, for the
+/// routes that don't use them, and refuses the target if it
+/// may not be checked.
+async fn resolve_allowed(checker: &Checker, host: &str, port: u16) -> Result<Target, StatusError> {
+    let target = dns::resolve(&checker.resolver, host, Some(port)).await;
+    if !checker.filter.allows(host, &target) {
+        return Err(StatusError::Forbidden);
+    }
+    if let Some(e) = target.unresolved {
+        return Err(e.into());
+    }
+    Ok(target)
+}
+
+/// Resolves `address` and pings the resulting target.
+async fn check(address: &str, query: &PingQuery, config: &Config, checker: &Checker) -> (Option<Target>, Result<Checked, StatusError>) {
+    check_as(address, query, config, checker, false).await
+}
+
+/// Checks a server the operator configured to monitor, which
+/// may be on a private network and is checked on its schedule
+/// regardless of how often clients ask for it.
+async fn check_monitored(address: &str, query: &PingQuery, config: &Config, checker: &Checker) -> (Option<Target>, Result<Checked, StatusError>) {
+    check_as(address, query, config, checker, true).await
+}
+
+/// Checks like [`check`], without the target filter and the
+/// limit of pings per target if `monitored`.
+async fn check_as(address: &str, query: &PingQuery, config: &Config, checker: &Checker, monitored: bool) -> (Option<Target>, Result<Checked, StatusError>) {
+    let cache = &checker.cache;
+    let (host, port, options) = match (parse_address(address, query.port), query.options(config)) {
+        (Ok((host, port)), Ok(options)) => (host, port, options),
+        (Err(e), _) => return (None, Err(e.into())),
+        (_, Err(e)) => return (None, Err(e)),
+    };
+
+    let key = CacheKey {
+        host: cache::normalize_host(host),
+        port,
+        protocol: options.protocol,
+        vhost: options.vhost.clone(),
+        proxy: query.proxy.clone(),
+    };
+    if !query.fresh.unwrap_or(false) {
+        if let Some((target, checked)) = cache.get(&key) {
+            return (Some(target), Ok(checked));
+        }
+    }
+
+    let resolver = checker.resolver.clone();
+    let targets = checker.targets.clone();
+    let filter = checker.filter.clone();
+    let metrics = checker.metrics.clone();
+    let server = match port {
+        Some(port) => format!("{}:{}", key.host, port),
+        None => key.host.clone(),
+    };
+    let host = host.to_string();
+    let retries = config.timeouts.attempts.max(1);
+    let span = tracing::info_span!("ping", server = %server);
+    let ping = async move {
+        let start = Instant::now();
+        let target = dns::resolve(&resolver, &host, port).instrument(tracing::info_span!("resolve")).await;
+        let resolve_ms = start.elapsed().as_secs_f64() * 1000.0;
+        if !monitored && !filter.allows(&host, &target) {
+            tracing::info!(resolve_ms, error = ?StatusError::Forbidden, "refused to ping");
+            return (target, Err(StatusError::Forbidden));
+        }
+        // Proxies resolve the host themselves
+        if let (Some(e), None) = (&target.unresolved, &options.proxy) {
+            tracing::warn!(resolve_ms, error = ?e, "can't resolve");
+            let e = StatusError::from(e.clone());
+            return (target, Err(e));
+        }
+        if !monitored && targets.take((target.connect_host(), target.port)).is_err() {
+            tracing::debug!(resolve_ms, error = ?StatusError::RateLimited, "refused to ping");
+            return (target, Err(StatusError::RateLimited));
+        }
+        let options = options.with_server_name(&target.host);
+        let _in_flight = metrics.track_ping();
+        let pinging = Instant::now();
+        let result = ping_java(&target.tcp_host(options.proxy.as_ref()), target.port, &options, retries).await.map_err(StatusError::from);
+        let ping_ms = pinging.elapsed().as_secs_f64() * 1000.0;
+        match &result {
+            Ok(ping) => tracing::info!(ip = ?target.ip, srv = target.srv, resolve_ms, ping_ms, latency_ms = ping.latency.map(|x| x.as_secs_f64() * 1000.0), "pinged"),
+            Err(e) => tracing::warn!(ip = ?target.ip, srv = target.srv, resolve_ms, ping_ms, error = ?e, "ping failed"),
+        }
+        metrics.record_ping(&server, &result, start.elapsed());
+        (target, result)
+    }.instrument(span);
+
+    // Monitored checks don't share the refusals of requests
+    let result = match monitored {
+        true => ping.await,
+        false => cache.coalesce(&key, ping).await,
+    };
+    match result {
+        (target, Ok(ping)) => {
+            let checked_at = SystemTime::now();
+            cache.insert(key, target.clone(), ping.clone(), checked_at);
+            (Some(target), Ok(Checked { ping, checked_at, age: None, stale: false }))
+        }
+        (target, Err(StatusError::RateLimited)) => {
+            match cache.get_latest(&key) {
+                Some((target, checked)) => (Some(target), Ok(checked)),
+                None => (Some(target), Err(StatusError::RateLimited)),
             }
         }
-        Err(_) => {
-            Err(StatusError::Timeout)
+        (target, Err(e)) => {
+            match cache.get_stale(&key) {
+                Some((target, checked)) => (Some(target), Ok(checked)),
+                None => (Some(target), Err(e)),
+            }
         }
     }
 }
 
-async fn ping(host: &str, port: u16) -> Result<StatusResponse, StdError> {
-    let mut connection_config = ConnectionConfig::build(host).with_port(port);
-    let status = connection_config.connect().await?.status().await?;
-    Ok(status.status)
+#[rocket::main]
+async fn main() -> ExitCode {
+    let cli = match cli::parse() {
+        Ok(x) => x,
+        Err(code) => return code,
+    };
+    match cli.command {
+        Some(Command::Check(args)) => cli::check(args).await,
+        Some(Command::Watch(args)) => cli::watch(args).await,
+        None | Some(Command::Serve) => {
+            if let Err(e) = rocket().launch().await {
+                error!("can't serve: {}", e);
+                return ExitCode::FAILURE;
+            }
+            ExitCode::SUCCESS
+        }
+    }
 }
 
-#[launch]
+/// The server with its configuration, state and routes.
 fn rocket() -> Rocket<Build> {
-    rocket::build().mount("/", routes![status, status_json])
+    let rocket = rocket::custom(figment());
+    let config: Config = rocket.figment().extract().unwrap_or_else(|e| panic!("invalid configuration: {}", e));
+    logging::init(&config.log);
+    let reporter = config.sentry.as_ref().map(report::init);
+    let resolver = dns::resolver(&config.dns).unwrap_or_else(|e| panic!("invalid configuration: {}", e));
+
+    let cache = Arc::new(StatusCache::new(Duration::from_secs(config.cache.ttl), Duration::from_secs(config.cache.stale_ttl), config.cache.max_entries));
+
+    let api_keys = ApiKeys::new(&config).unwrap_or_else(|e| panic!("invalid configuration: {}", e));
+    let client_limiter: RateLimiter<IpAddr> = RateLimiter::new(config.rate_limit.per_minute, config.rate_limit.burst);
+
+    let banner_renderer = BannerRenderer::load(&config.banner.font)
+        .map_err(|e| warn!("banners are disabled: {}", e))
+        .ok();
+
+    let metrics = Arc::new(Metrics::new());
+    let checker = Checker {
+        resolver,
+        cache,
+        targets: TargetLimiter::with_interval(Duration::from_secs(config.rate_limit.target_interval)),
+        filter: Arc::new(TargetFilter::new(&config.targets).unwrap_or_else(|e| panic!("invalid configuration: {}", e))),
+        metrics: metrics.clone(),
+    };
+
+    #[cfg(unix)]
+    let rocket = rocket.attach(systemd::Systemd);
+
+    rocket
+        // Before the access log, which logs the size sent
+        .attach(ConditionalGet)
+        .attach(Compression)
+        .attach(RequestTracing)
+        .attach(RequestMetrics)
+        .attach(ProblemStatuses)
+        .attach(RateLimitHeaders)
+        .attach(SecurityHeaders)
+        .attach(Cors)
+        .attach(Monitor)
+        .manage(checker)
+        .manage(metrics)
+        .manage(client_limiter)
+        .manage(banner_renderer)
+        .manage(api_keys)
+        .manage(config)
+        .manage(reporter)
+        .manage(Started::now())
+        .mount("/v1", v1_routes())
+        .mount("/v2", v2_routes())
+        .mount("/", unversioned_routes())
+        .mount("/", routes![export_metrics, probe, healthz, readyz, self_status])
+        .mount("/", cors::routes())
+        .register("/", catchers::catchers())
+}
+
+/// Rocket's configuration sources with the configuration file
+/// of the checker, `mcstatus-checker.toml` or the one at
+/// `MCSTATUS_CONFIG`, between `Rocket.toml` and the `ROCKET_`
+/// environment variables. It has the same profiles.
+fn figment() -> Figment {
+    Figment::from(rocket::Config::default())
+        .merge(Toml::file(Env::var_or("ROCKET_CONFIG", "Rocket.toml")).nested())
+        .merge(Toml::file(Env::var_or("MCSTATUS_CONFIG", "mcstatus-checker.toml")).nested())
+        .merge(Env::prefixed("ROCKET_").ignore(&["PROFILE"]).global())
+        .select(Profile::from_env_or("ROCKET_PROFILE", rocket::Config::DEFAULT_PROFILE))
+}
+
+/// The routes of version 1 of the API. Changes to the shape
+/// of their responses go into a new version mounted next to
+/// this one, so existing consumers keep working.
+fn v1_routes() -> Vec<Route> {
+    routes![status, status_json, status_bedrock, status_query, motd, motd_text, status_players, status_version, status_badge, status_banner, status_icon, status_uptime, status_peaks, history_players, history_csv, incidents, sessions, incidents_feed, server_feed, group_status, raw, status_versions, status_any, batch, batch_stream, purge_cache, clear_cache, list_servers, add_server, update_server, remove_server, import_servers, export_servers, reload]
+}
+
+/// The routes of version 2 of the API, which are the same as
+/// the ones of `/v1` but respond errors as objects of their
+/// code, message and cause.
+fn v2_routes() -> Vec<Route> {
+    v1_routes()
+}
+
+/// Whether a request is for version 2 of the API.
+fn is_v2(uri: &Origin<'_>) -> bool {
+    uri.path().starts_with("/v2/")
+}
+
+/// The routes from before the API was versioned, the same as
+/// the ones of `/v1`. They rank after every default rank so
+/// that `/v1/...` is never taken for a server address.
+fn unversioned_routes() -> Vec<Route> {
+    v1_routes().into_iter()
+        .map(|mut route| {
+            route.rank += 12;
+            route
+        })
+        .collect()
 }
 /*
 #[tokio::main]
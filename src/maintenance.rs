@@ -0,0 +1,108 @@
+//! Maintenance windows of monitored servers, during which
+//! they are checked but nobody is notified and the checks
+//! don't count towards their uptime.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// A time the server is under maintenance, either once
+/// between two points in time or every week.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum MaintenanceWindow {
+    /// From `start` until `end` in seconds since the Unix
+    /// epoch.
+    Once {
+        start: u64,
+        end: u64,
+    },
+
+    /// From `start` until `end` in UTC on the `days` of the
+    /// week, every day if there are none. Windows ending
+    /// before they start end on the next day.
+    Weekly {
+        #[serde(default)]
+        days: Vec<Weekday>,
+        start: TimeOfDay,
+        end: TimeOfDay,
+    },
+}
+
+/// A day of the week, configured as `mon`, `monday` and so
+/// on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Weekday(u64);
+
+/// The names of the days of the week, by their number.
+const DAYS: [&str; 7] = ["sunday", "monday", "tuesday", "wednesday", "thursday", "friday", "saturday"];
+
+impl TryFrom<String> for Weekday {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        let lower = name.to_ascii_lowercase();
+        DAYS.iter()
+            .position(|x| *x == lower || (lower.len() == 3 && x.starts_with(&lower)))
+            .map(|x| Weekday(x as u64))
+            .ok_or_else(|| format!("invalid day of the week `{}`", name))
+    }
+}
+
+impl From<Weekday> for String {
+    fn from(day: Weekday) -> Self {
+        DAYS[day.0 as usize].to_string()
+    }
+}
+
+/// A time of the day as `HH:MM`, in seconds since midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(u64);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(time: String) -> Result<Self, Self::Error> {
+        let parsed = time.split_once(':')
+            .and_then(|(h, m)| Some((h.parse::<u64>().ok()?, m.parse::<u64>().ok()?)))
+            .filter(|&(h, m)| h <= 24 && m < 60 && h * 60 + m <= 24 * 60);
+        match parsed {
+            Some((h, m)) => Ok(TimeOfDay((h * 60 + m) * 60)),
+            None => Err(format!("invalid time `{}`, expected HH:MM", time)),
+        }
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        format!("{:02}:{:02}", time.0 / 3600, time.0 / 60 % 60)
+    }
+}
+
+impl MaintenanceWindow {
+    /// Whether `at` is within the window.
+    pub fn contains(&self, at: SystemTime) -> bool {
+        let secs = at.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs());
+        match self {
+            MaintenanceWindow::Once { start, end } => (*start..*end).contains(&secs),
+            MaintenanceWindow::Weekly { days, start, end } => {
+                let (day, time) = (secs / DAY, secs % DAY);
+                // The Unix epoch was a Thursday
+                let on = |day: u64| days.is_empty() || days.contains(&Weekday((day + 4) % 7));
+                if start <= end {
+                    on(day) && (start.0..end.0).contains(&time)
+                } else {
+                    (on(day) && time >= start.0) || (day > 0 && on(day - 1) && time < end.0)
+                }
+            }
+        }
+    }
+}
+
+/// Whether `at` is within any of the `windows`.
+pub fn contains(windows: &[MaintenanceWindow], at: SystemTime) -> bool {
+    windows.iter().any(|x| x.contains(at))
+}
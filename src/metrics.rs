@@ -0,0 +1,251 @@
+//! Prometheus metrics of the requests and pings, scraped
+//! from `/metrics`.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use prometheus::{Encoder, Gauge, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use prometheus::core::Collector;
+use rocket::{Data, Request, Response};
+use rocket::http::ContentType;
+use rocket::fairing::{Fairing, Info, Kind};
+use mcstatus_checker::java::Ping;
+use crate::error::StatusError;
+
+/// The most servers to keep gauges for, so a public instance
+/// can't be made to export unbounded series.
+const MAX_SERVERS: usize = 1000;
+
+/// The buckets of the ping latency in seconds.
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// The buckets of the whole ping in seconds, which includes
+/// resolving, connecting and waiting up to the timeouts.
+const DURATION_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// The `server` label of the servers beyond the limit.
+const OTHER_SERVERS: &str = "other";
+
+/// All metrics, registered in their own registry.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    pings: IntCounterVec,
+    latency: HistogramVec,
+    duration: HistogramVec,
+    errors: IntCounterVec,
+    in_flight: IntGauge,
+    up: IntGaugeVec,
+    players_online: IntGaugeVec,
+    players_max: IntGaugeVec,
+    servers: Mutex<HashSet<String>>,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let registry = Registry::new();
+        let requests = IntCounterVec::new(
+            Opts::new("mcstatus_http_requests_total", "HTTP requests by route and status code"),
+            &["route", "status"],
+        ).unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new("mcstatus_http_request_duration_seconds", "Time to respond HTTP requests by route"),
+            &["route"],
+        ).unwrap();
+        let pings = IntCounterVec::new(
+            Opts::new("minecraft_pings_total", "Java Edition pings by result, `ok` or `error`"),
+            &["result"],
+        ).unwrap();
+        let latency = HistogramVec::new(
+            HistogramOpts::new("minecraft_ping_latency_seconds", "Round-trip time of the ping packet")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            &["server"],
+        ).unwrap();
+        let duration = HistogramVec::new(
+            HistogramOpts::new("minecraft_ping_duration_seconds", "Time from resolving the server until its status, by result")
+                .buckets(DURATION_BUCKETS.to_vec()),
+            &["server", "result"],
+        ).unwrap();
+        let errors = IntCounterVec::new(
+            Opts::new("minecraft_ping_errors_total", "Failed Java Edition pings by error"),
+            &["server", "error"],
+        ).unwrap();
+        let in_flight = IntGauge::new("minecraft_pings_in_flight", "Java Edition pings waiting for their server").unwrap();
+        let up = IntGaugeVec::new(
+            Opts::new("minecraft_server_up", "Whether the last ping of the server succeeded"),
+            &["server"],
+        ).unwrap();
+        let players_online = IntGaugeVec::new(
+            Opts::new("minecraft_players_online", "The number of players online at the last ping"),
+            &["server"],
+        ).unwrap();
+        let players_max = IntGaugeVec::new(
+            Opts::new("minecraft_players_max", "The maximum number of players at the last ping"),
+            &["server"],
+        ).unwrap();
+
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(request_duration.clone())).unwrap();
+        registry.register(Box::new(pings.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        registry.register(Box::new(duration.clone())).unwrap();
+        registry.register(Box::new(errors.clone())).unwrap();
+        registry.register(Box::new(in_flight.clone())).unwrap();
+        registry.register(Box::new(up.clone())).unwrap();
+        registry.register(Box::new(players_online.clone())).unwrap();
+        registry.register(Box::new(players_max.clone())).unwrap();
+
+        Metrics {
+            registry,
+            requests,
+            request_duration,
+            pings,
+            latency,
+            duration,
+            errors,
+            in_flight,
+            up,
+            players_online,
+            players_max,
+            servers: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Records the result of pinging `server`, which took
+    /// `duration`. Its gauges are only kept for the first
+    /// servers up to the limit, the others count as `other`.
+    pub fn record_ping(&self, server: &str, result: &Result<Ping, StatusError>, duration: Duration) {
+        let outcome = if result.is_ok() { "ok" } else { "error" };
+        self.pings.with_label_values(&[outcome]).inc();
+
+        let tracked = {
+            let mut servers = self.servers.lock().unwrap();
+            servers.contains(server) || servers.len() < MAX_SERVERS && servers.insert(server.to_string())
+        };
+        let label = if tracked { server } else { OTHER_SERVERS };
+        self.duration.with_label_values(&[label, outcome]).observe(duration.as_secs_f64());
+        if let Err(e) = result {
+            self.errors.with_label_values(&[label, format!("{:?}", e).as_str()]).inc();
+        }
+        if !tracked {
+            return;
+        }
+
+        match result {
+            Ok(ping) => {
+                self.up.with_label_values(&[server]).set(1);
+                self.players_online.with_label_values(&[server]).set(ping.status.players.online as i64);
+                self.players_max.with_label_values(&[server]).set(ping.status.players.max as i64);
+                if let Some(latency) = ping.latency {
+                    self.latency.with_label_values(&[server]).observe(latency.as_secs_f64());
+                }
+            }
+            Err(_) => {
+                self.up.with_label_values(&[server]).set(0);
+            }
+        }
+    }
+
+    /// Counts a ping as in flight until the returned guard is
+    /// dropped.
+    pub fn track_ping(&self) -> InFlight {
+        self.in_flight.inc();
+        InFlight(self.in_flight.clone())
+    }
+
+    /// The number of pings in flight.
+    pub fn pings_in_flight(&self) -> i64 {
+        self.in_flight.get()
+    }
+
+    /// Encodes all metrics in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        encode(&self.registry)
+    }
+}
+
+/// A ping in flight, which is done when dropped, even if it
+/// was cancelled.
+pub struct InFlight(IntGauge);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// The content type of the Prometheus text format.
+pub fn content_type() -> ContentType {
+    ContentType::new("text", "plain").with_params(("version", "0.0.4"))
+}
+
+fn encode(registry: &Registry) -> String {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&registry.gather(), &mut buffer).unwrap_or_default();
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+/// Encodes the result of a single ping the way the
+/// blackbox exporter does, taking `duration` in total.
+pub fn encode_probe(ping: Option<&Ping>, duration: Duration) -> String {
+    let registry = Registry::new();
+    let register = |collector: Box<dyn Collector>| registry.register(collector).unwrap();
+
+    let success = IntGauge::new("probe_success", "Whether the server answered the ping").unwrap();
+    success.set(ping.is_some() as i64);
+    register(Box::new(success));
+
+    let probe_duration = Gauge::new("probe_duration_seconds", "How long the probe took").unwrap();
+    probe_duration.set(duration.as_secs_f64());
+    register(Box::new(probe_duration));
+
+    if let Some(ping) = ping {
+        let online = IntGauge::new("minecraft_players_online", "The number of players online").unwrap();
+        online.set(ping.status.players.online as i64);
+        register(Box::new(online));
+
+        let max = IntGauge::new("minecraft_players_max", "The maximum number of players").unwrap();
+        max.set(ping.status.players.max as i64);
+        register(Box::new(max));
+
+        if let Some(latency) = ping.latency {
+            let gauge = Gauge::new("minecraft_ping_latency_seconds", "Round-trip time of the ping packet").unwrap();
+            gauge.set(latency.as_secs_f64());
+            register(Box::new(gauge));
+        }
+    }
+
+    encode(&registry)
+}
+
+/// When the request arrived, to measure how long it takes.
+struct RequestStart(Instant);
+
+/// Records every request in the managed [`Metrics`].
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let metrics = match request.rocket().state::<Arc<Metrics>>() {
+            Some(metrics) => metrics,
+            None => return,
+        };
+        let route = request.route().and_then(|x| x.name.as_deref()).unwrap_or("none");
+        let start = request.local_cache(|| RequestStart(Instant::now()));
+        metrics.requests.with_label_values(&[route, response.status().code.to_string().as_str()]).inc();
+        metrics.request_duration.with_label_values(&[route]).observe(start.0.elapsed().as_secs_f64());
+    }
+}
@@ -0,0 +1,469 @@
+//! Checking the monitored servers on a schedule and storing
+//! the results in the [`History`].
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use rocket::{Build, Orbit, Rocket, Shutdown};
+use rocket::fairing::{self, Fairing, Info, Kind};
+use tokio::sync::{watch, Semaphore};
+use tokio::time::MissedTickBehavior;
+use tracing::Instrument;
+use mcstatus_checker::java::StatusResponse;
+use crate::error::StatusError;
+use crate::changes::Fingerprint;
+use crate::config::{Config, HistoryConfig, MonitorConfig, MonitoredServer};
+use crate::history::History;
+use crate::maintenance;
+use crate::mqtt::Publisher;
+use crate::notify::{Notifier, PropertyChange, StatusChange};
+use crate::report;
+use crate::servers::MonitoredServers;
+use crate::{check_monitored, cron, Checker, PingQuery};
+
+/// How often old results of the history are compacted.
+const COMPACT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Opens the history on ignite if there are monitored servers
+/// or an admin token to add them with, managed as
+/// `Option<History>` along with the `Arc<MonitoredServers>`.
+/// Checks the servers after liftoff, compacting the history
+/// in the background. On shutdown, waits for the scheduled
+/// checks to finish and closes the history.
+pub struct Monitor;
+
+/// Whether the scheduler stopped after shutdown was requested.
+struct Drained(watch::Sender<bool>);
+
+#[rocket::async_trait]
+impl Fairing for Monitor {
+    fn info(&self) -> Info {
+        Info {
+            name: "Monitor",
+            kind: Kind::Ignite | Kind::Liftoff | Kind::Shutdown,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = match rocket.state::<Config>() {
+            Some(config) => config,
+            None => return Ok(rocket.manage(None::<History>)),
+        };
+
+        let history = if config.monitor.servers.is_empty() && config.admin_token.is_none() {
+            None
+        } else {
+            match History::open(&config.history.path).await {
+                Ok(history) => Some(history),
+                Err(e) => {
+                    error!("can't open the history at {}: {}", config.history.path, e);
+                    return Err(rocket);
+                }
+            }
+        };
+        match MonitoredServers::load(&config.monitor, history.clone()).await {
+            Ok(servers) => {
+                let settings = Arc::new(MonitorSettings::new(config.monitor.clone()));
+                let drained = Arc::new(Drained(watch::channel(false).0));
+                Ok(rocket.manage(history).manage(Arc::new(servers)).manage(settings).manage(Arc::new(Heartbeat::default())).manage(drained))
+            }
+            Err(e) => {
+                error!("can't read the monitored servers from {}: {}", config.history.path, e);
+                Err(rocket)
+            }
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let (settings, heartbeat, drained) = (rocket.state::<Arc<MonitorSettings>>(), rocket.state::<Arc<Heartbeat>>(), rocket.state::<Arc<Drained>>());
+        if let (Some(config), Some(checker), Some(Some(history)), Some(servers), Some(settings), Some(heartbeat), Some(drained)) = (rocket.state::<Config>(), rocket.state::<Checker>(), rocket.state::<Option<History>>(), rocket.state::<Arc<MonitoredServers>>(), settings, heartbeat, drained) {
+            let (history, drained) = (history.clone(), drained.clone());
+            tokio::spawn(compact(config.history.clone(), history.clone()));
+            let scheduler = run(config.clone(), checker.clone(), history.clone(), servers.clone(), settings.clone(), heartbeat.clone(), rocket.shutdown());
+            tokio::spawn(async move {
+                scheduler.await;
+                // The checks store their results right away, so
+                // closing waits for the last ones
+                history.close().await;
+                drained.0.send_replace(true);
+            });
+        }
+    }
+
+    async fn on_shutdown(&self, rocket: &Rocket<Orbit>) {
+        let Some(drained) = rocket.state::<Arc<Drained>>() else {
+            return;
+        };
+        // Without history the scheduler never started
+        if !matches!(rocket.state::<Option<History>>(), Some(Some(_))) {
+            return;
+        }
+        let _ = drained.0.subscribe().wait_for(|x| *x).await;
+    }
+}
+
+/// Whether a server is considered online, and the checks
+/// since that disagree.
+struct ServerState {
+    online: bool,
+
+    /// When the server was first seen in its current state.
+    since: SystemTime,
+
+    /// The number of checks in a row with the other result.
+    disagreeing: u32,
+
+    /// When the first of these checks was.
+    disagreeing_since: SystemTime,
+
+    /// How often the time between checks has been doubled
+    /// since the server went offline.
+    backoff: u32,
+}
+
+impl ServerState {
+    fn new(online: bool, at: SystemTime) -> ServerState {
+        ServerState { online, since: at, disagreeing: 0, disagreeing_since: at, backoff: 0 }
+    }
+
+    /// Counts a check of the server, switching its state after
+    /// `threshold` disagreeing checks in a row. Returns when the
+    /// first of them was and how long the server was in its
+    /// previous state if it switched.
+    fn observe(&mut self, online: bool, at: SystemTime, threshold: u32) -> Option<(SystemTime, Duration)> {
+        if online == self.online {
+            self.disagreeing = 0;
+            return None;
+        }
+
+        if self.disagreeing == 0 {
+            self.disagreeing_since = at;
+        }
+        self.disagreeing += 1;
+        if self.disagreeing < threshold.max(1) {
+            return None;
+        }
+
+        let previous_duration = self.disagreeing_since.duration_since(self.since).unwrap_or_default();
+        *self = ServerState::new(online, self.disagreeing_since);
+        Some((self.since, previous_duration))
+    }
+}
+
+/// The settings of the monitor in use, which are replaced
+/// when the configuration is reloaded.
+pub struct MonitorSettings {
+    current: Mutex<Arc<MonitorConfig>>,
+}
+
+impl MonitorSettings {
+    pub fn new(config: MonitorConfig) -> MonitorSettings {
+        MonitorSettings { current: Mutex::new(Arc::new(config)) }
+    }
+
+    pub fn get(&self) -> Arc<MonitorConfig> {
+        self.current.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, config: MonitorConfig) {
+        *self.current.lock().unwrap() = Arc::new(config);
+    }
+}
+
+/// When the scheduler last looked for servers that are due,
+/// to tell whether it is running.
+#[derive(Default)]
+pub struct Heartbeat {
+    last: Mutex<Option<Instant>>,
+}
+
+impl Heartbeat {
+    fn beat(&self) {
+        *self.last.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Whether the scheduler looked within `period`.
+    pub fn alive(&self, period: Duration) -> bool {
+        self.last.lock().unwrap().is_some_and(|x| x.elapsed() <= period)
+    }
+}
+
+/// How often the scheduler looks whether the running checks
+/// are done on shutdown.
+const DRAIN_POLL: Duration = Duration::from_millis(100);
+
+/// How often the scheduler looks for servers that are due.
+const SCHEDULER_TICK: Duration = Duration::from_secs(1);
+
+/// What the checks of the monitored servers share.
+struct Scheduler {
+    config: Config,
+    checker: Checker,
+    history: History,
+    settings: Arc<MonitorSettings>,
+
+    /// The notifier of the settings it was built from.
+    notifier: Mutex<(Arc<MonitorConfig>, Arc<Notifier>)>,
+    publisher: Option<Publisher>,
+    states: Mutex<HashMap<String, ServerState>>,
+
+    /// The servers being checked right now.
+    running: Mutex<HashSet<String>>,
+
+    /// Whether shutdown was requested, so the checks waiting
+    /// for their turn are skipped.
+    stopping: AtomicBool,
+}
+
+impl Scheduler {
+    /// The notifier of the current settings, built again after
+    /// they were reloaded.
+    fn notifier(&self) -> Arc<Notifier> {
+        let monitor = self.settings.get();
+        let mut notifier = self.notifier.lock().unwrap();
+        if !Arc::ptr_eq(&notifier.0, &monitor) {
+            *notifier = (monitor.clone(), Arc::new(Notifier::new(&monitor)));
+        }
+        notifier.1.clone()
+    }
+
+    /// The time until the next check of `address` if it has
+    /// been offline for long enough to back off, doubling with
+    /// every check up to the `max_backoff`.
+    fn backoff(&self, address: &str, interval: Duration) -> Option<Duration> {
+        let monitor = self.settings.get();
+        let mut states = self.states.lock().unwrap();
+        let state = states.get_mut(address)?;
+        let offline_for = SystemTime::now().duration_since(state.since).unwrap_or_default();
+        if state.online || monitor.backoff_after == 0 || offline_for < Duration::from_secs(monitor.backoff_after) {
+            state.backoff = 0;
+            return None;
+        }
+
+        state.backoff = (state.backoff + 1).min(16);
+        let max = Duration::from_secs(monitor.max_backoff).max(interval);
+        Some(interval.saturating_mul(1 << state.backoff).min(max))
+    }
+
+    /// The state of `address` before its first check, offline
+    /// if it has an open incident so restarts don't notify
+    /// again and online otherwise.
+    async fn initial_state(&self, address: &str) -> ServerState {
+        match self.history.open_incident(address).await {
+            Ok(Some(started_at)) => ServerState::new(false, started_at),
+            Ok(None) => ServerState::new(true, SystemTime::now()),
+            Err(e) => {
+                warn!("can't read the incidents of {}: {}", address, e);
+                ServerState::new(true, SystemTime::now())
+            }
+        }
+    }
+
+    /// Compares the watched properties of `server` with the
+    /// ones at its last successful check, notifying of the
+    /// changes unless it is under maintenance.
+    async fn detect_changes(&self, server: &MonitoredServer, status: &StatusResponse, checked_at: SystemTime, maintenance: bool) {
+        let address = server.address.as_str();
+        let fingerprint = Fingerprint::of(status);
+        let previous = match self.history.fingerprint(address).await {
+            Ok(previous) => previous,
+            Err(e) => {
+                warn!("can't read the properties of {}: {}", address, e);
+                return;
+            }
+        };
+        if previous.as_ref() == Some(&fingerprint) {
+            return;
+        }
+        if let Err(e) = self.history.store_fingerprint(address, &fingerprint).await {
+            warn!("can't store the properties of {}: {}", address, e);
+            report::storage_error(Some(address), &e);
+        }
+
+        // The first check has nothing to compare with, and
+        // changes during maintenance are expected
+        let Some(previous) = previous.filter(|_| !maintenance) else {
+            return;
+        };
+        for (property, old, new) in previous.changes(&fingerprint) {
+            let change = PropertyChange { server: address.to_string(), property, old, new, checked_at };
+            self.notifier().notify_property(server, &change).await;
+        }
+    }
+
+    /// Checks `server`, stores the result and notifies if it
+    /// went online or offline.
+    async fn check(&self, server: &MonitoredServer) {
+        let address = server.address.as_str();
+        if !self.states.lock().unwrap().contains_key(address) {
+            let state = self.initial_state(address).await;
+            self.states.lock().unwrap().entry(address.to_string()).or_insert(state);
+        }
+
+        let checked_at = SystemTime::now();
+        let query = PingQuery {
+            fresh: Some(true),
+            protocol: server.protocol,
+            timeout_ms: server.timeout_ms,
+            ..PingQuery::default()
+        };
+        let (_, result) = check_monitored(address, &query, &self.config, &self.checker).await;
+        let result = result.and_then(|x| match &server.expected_version {
+            Some(expected) if !x.ping.status.version.name.contains(expected.as_str()) => Err(StatusError::UnexpectedVersion),
+            _ => Ok(x),
+        });
+        // A stale status means the server couldn't be pinged
+        let ping = result.as_ref().ok().filter(|x| !x.stale).map(|x| &x.ping);
+        let maintenance = maintenance::contains(&server.maintenance, checked_at);
+        if let Err(e) = self.history.record(address, checked_at, ping, maintenance).await {
+            warn!("can't store the check of {}: {}", address, e);
+            report::storage_error(Some(address), &e);
+        }
+        if let Some(ping) = ping {
+            let timeout = Duration::from_secs(self.config.history.session_timeout_minutes * 60);
+            if let Err(e) = self.history.track_sessions(address, checked_at, &ping.status.players, timeout).await {
+                warn!("can't store the sessions of {}: {}", address, e);
+                report::storage_error(Some(address), &e);
+            }
+            if self.settings.get().notify_changes {
+                self.detect_changes(server, &ping.status, checked_at, maintenance).await;
+            }
+        }
+        if let Some(publisher) = &self.publisher {
+            publisher.publish(address, ping);
+        }
+
+        // The state is kept as it was, so servers still
+        // offline after maintenance are notified of
+        if maintenance {
+            return;
+        }
+        let online = ping.is_some();
+        let monitor = self.settings.get();
+        let threshold = match online {
+            true => server.up_after.unwrap_or(monitor.up_after),
+            false => server.down_after.unwrap_or(monitor.down_after),
+        };
+        let switched = self.states.lock().unwrap()
+            .entry(address.to_string())
+            .or_insert_with(|| ServerState::new(online, checked_at))
+            .observe(online, checked_at, threshold);
+        if let Some((since, previous_duration)) = switched {
+            let incident = if online {
+                self.history.end_incident(address, since).await
+            } else {
+                let error = result.as_ref().err().map(|e| format!("{:?}", e));
+                self.history.start_incident(address, since, error.as_deref()).await
+            };
+            if let Err(e) = incident {
+                warn!("can't store the incident of {}: {}", address, e);
+                report::storage_error(Some(address), &e);
+            }
+
+            let change = StatusChange {
+                server: address.to_string(),
+                ping: ping.cloned(),
+                checked_at,
+                previous_duration,
+            };
+            self.notifier().notify(server, &change).await;
+        }
+    }
+}
+
+/// Checks every monitored server each of its intervals or at
+/// the minutes of its schedule, a limited number at once,
+/// and notifies of the ones that went online or offline.
+/// Servers added or removed at runtime are picked up on the
+/// next tick. Stops on `shutdown`, waiting up to
+/// `monitor.drain_timeout` for the running checks.
+async fn run(config: Config, checker: Checker, history: History, servers: Arc<MonitoredServers>, settings: Arc<MonitorSettings>, heartbeat: Arc<Heartbeat>, mut shutdown: Shutdown) {
+    let mut tick = tokio::time::interval(SCHEDULER_TICK);
+    tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let permits = Arc::new(Semaphore::new(config.batch.concurrency.max(1)));
+    let monitor = settings.get();
+    let scheduler = Arc::new(Scheduler {
+        notifier: Mutex::new((monitor.clone(), Arc::new(Notifier::new(&monitor)))),
+        settings,
+        publisher: config.monitor.mqtt.as_ref().map(Publisher::connect),
+        config,
+        checker,
+        history,
+        states: Mutex::new(HashMap::new()),
+        running: Mutex::new(HashSet::new()),
+        stopping: AtomicBool::new(false),
+    });
+    let mut next_checks: HashMap<String, Instant> = HashMap::new();
+
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {}
+            _ = &mut shutdown => break,
+        }
+        heartbeat.beat();
+        let now = Instant::now();
+        let servers = servers.servers();
+        next_checks.retain(|address, _| servers.iter().any(|x| x.address == *address));
+        scheduler.states.lock().unwrap().retain(|address, _| servers.iter().any(|x| x.address == *address));
+
+        for server in servers {
+            if next_checks.get(&server.address).is_some_and(|x| *x > now) {
+                continue;
+            }
+            if server.schedule.as_ref().is_some_and(|x| !x.matches(SystemTime::now())) {
+                continue;
+            }
+            // Servers taking longer than their interval are
+            // checked again once they are done
+            if !scheduler.running.lock().unwrap().insert(server.address.clone()) {
+                continue;
+            }
+            // Scheduled servers are checked once in every
+            // matching minute
+            let interval = Duration::from_secs(server.interval.unwrap_or(scheduler.settings.get().interval).max(1));
+            let delay = match &server.schedule {
+                Some(_) => cron::until_next_minute(SystemTime::now()),
+                None => interval,
+            };
+            let delay = scheduler.backoff(&server.address, interval).map_or(delay, |x| x.max(delay));
+            next_checks.insert(server.address.clone(), now + delay);
+
+            let (scheduler, permits) = (scheduler.clone(), permits.clone());
+            let span = tracing::info_span!("scheduled_check", server = %server.address);
+            tokio::spawn(async move {
+                if let Ok(_permit) = permits.acquire_owned().await {
+                    if !scheduler.stopping.load(Ordering::Relaxed) {
+                        scheduler.check(&server).await;
+                    }
+                }
+                scheduler.running.lock().unwrap().remove(&server.address);
+            }.instrument(span));
+        }
+    }
+
+    scheduler.stopping.store(true, Ordering::Relaxed);
+    let deadline = Instant::now() + Duration::from_secs(scheduler.settings.get().drain_timeout);
+    while !scheduler.running.lock().unwrap().is_empty() {
+        if Instant::now() >= deadline {
+            warn!("stopping without waiting for the checks of {} servers", scheduler.running.lock().unwrap().len());
+            break;
+        }
+        tokio::time::sleep(DRAIN_POLL).await;
+    }
+}
+
+/// Downsamples and removes old results of the history every
+/// `COMPACT_INTERVAL`.
+async fn compact(config: HistoryConfig, history: History) {
+    let mut interval = tokio::time::interval(COMPACT_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = history.compact(&config).await {
+            warn!("can't compact the history: {}", e);
+            report::storage_error(None, &e);
+        }
+    }
+}
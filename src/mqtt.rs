@@ -0,0 +1,72 @@
+//! Publishing the results of scheduled checks to an MQTT
+//! broker, for home automation and displays.
+
+use std::time::Duration;
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use mcstatus_checker::java::Ping;
+use crate::config::MqttConfig;
+
+/// The number of publishes queued while the broker can't be
+/// reached. Further ones are dropped until it is again.
+const QUEUE_CAPACITY: usize = 100;
+
+/// How long to wait before reconnecting to the broker.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Publishes check results to the configured broker.
+pub struct Publisher {
+    client: AsyncClient,
+    prefix: String,
+    retain: bool,
+}
+
+impl Publisher {
+    /// Connects to the broker of `config` in the background,
+    /// reconnecting whenever the connection is lost.
+    pub fn connect(config: &MqttConfig) -> Publisher {
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+        }
+
+        let (client, mut event_loop) = AsyncClient::new(options, QUEUE_CAPACITY);
+        let host = config.host.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = event_loop.poll().await {
+                    warn!("lost the connection to the MQTT broker {}: {}", host, e);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        });
+
+        Publisher {
+            client,
+            prefix: config.topic_prefix.trim_end_matches('/').to_string(),
+            retain: config.retain,
+        }
+    }
+
+    /// Publishes whether `server` is `online`, and its players,
+    /// maximum players and latency in milliseconds if it is,
+    /// each on its own topic. Dropped if the queue is full, so
+    /// checks don't wait for a broker that is down.
+    pub fn publish(&self, server: &str, ping: Option<&Ping>) {
+        let mut messages = vec![("online", ping.is_some().to_string())];
+        if let Some(ping) = ping {
+            messages.push(("players", ping.status.players.online.to_string()));
+            messages.push(("players_max", ping.status.players.max.to_string()));
+            if let Some(latency) = ping.latency {
+                messages.push(("latency", latency.as_millis().to_string()));
+            }
+        }
+
+        for (topic, payload) in messages {
+            let topic = format!("{}/{}/{}", self.prefix, server, topic);
+            if let Err(e) = self.client.try_publish(&topic, QoS::AtLeastOnce, self.retain, payload) {
+                warn!("can't publish {}, dropped it: {}", topic, e);
+            }
+        }
+    }
+}
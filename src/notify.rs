@@ -0,0 +1,284 @@
+//! Notifications about monitored servers going online or
+//! offline.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures::future::{join_all, BoxFuture, FutureExt};
+use serde::Serialize;
+use mcstatus_checker::java::Ping;
+use crate::config::{MonitorConfig, MonitoredServer, NotifyConfig, TelegramConfig, TemplateConfig};
+use crate::changes::Property;
+use crate::{discord, slack, telegram, template};
+
+/// How long to wait for a webhook to answer.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A monitored server going online or offline.
+#[derive(Debug)]
+pub struct StatusChange {
+    /// The address of the server as configured.
+    pub server: String,
+
+    /// The status if the server went online.
+    pub ping: Option<Ping>,
+
+    /// When the change was detected.
+    pub checked_at: SystemTime,
+
+    /// How long the server was in its previous state, as far
+    /// as the monitor has seen it.
+    pub previous_duration: Duration,
+}
+
+impl StatusChange {
+    pub fn online(&self) -> bool {
+        self.ping.is_some()
+    }
+
+    /// The variables of the notification templates.
+    fn variables(&self) -> Vec<(&'static str, String)> {
+        let duration = format_duration(self.previous_duration);
+        let status = self.ping.as_ref().map(|x| &x.status);
+        vec![
+            ("server", self.server.clone()),
+            ("status", if self.online() { "online" } else { "offline" }.to_string()),
+            ("players_online", status.map_or(String::new(), |x| x.players.online.to_string())),
+            ("players_max", status.map_or(String::new(), |x| x.players.max.to_string())),
+            ("version", status.map_or(String::new(), |x| x.version.name.clone())),
+            ("motd", status.map_or(String::new(), |x| x.motd.clone())),
+            ("latency", self.ping.as_ref().and_then(|x| x.latency).map_or(String::new(), |x| format!("{}ms", x.as_millis()))),
+            ("downtime", if self.online() { duration.clone() } else { String::new() }),
+            ("uptime", if self.online() { String::new() } else { duration }),
+        ]
+    }
+
+    /// The text about the change from the template for its
+    /// new status.
+    pub fn message(&self, templates: &TemplateConfig) -> String {
+        let template = if self.online() { &templates.online } else { &templates.offline };
+        template::render(template, &self.variables())
+    }
+}
+
+/// A watched property of a monitored server that changed
+/// between two checks.
+#[derive(Debug)]
+pub struct PropertyChange {
+    /// The address of the server as configured.
+    pub server: String,
+
+    pub property: Property,
+
+    /// The value at the check before, the hash for the icon.
+    pub old: String,
+
+    /// The value now.
+    pub new: String,
+
+    /// When the change was detected.
+    pub checked_at: SystemTime,
+}
+
+impl PropertyChange {
+    /// The text about the change from its template.
+    pub fn message(&self, templates: &TemplateConfig) -> String {
+        let variables = [
+            ("server", self.server.clone()),
+            ("property", self.property.name().to_string()),
+            ("old", self.old.clone()),
+            ("new", self.new.clone()),
+        ];
+        template::render(&templates.changed, &variables)
+    }
+}
+
+/// Formats `duration` like "2d 3h", "1h 5m" or "42s".
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+/// The JSON body POSTed to webhooks.
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    server: &'a str,
+    online: bool,
+
+    /// When the change was detected, in seconds since the Unix
+    /// epoch.
+    checked_at: u64,
+
+    /// How long the server was offline in seconds, if it went
+    /// online again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    downtime: Option<u64>,
+
+    /// How long the server was online in seconds, if it went
+    /// offline.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uptime: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    players_online: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    players_max: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    motd: Option<&'a str>,
+
+    /// The text of the notification.
+    message: &'a str,
+}
+
+/// The JSON body POSTed to webhooks when a watched property
+/// changed.
+#[derive(Debug, Serialize)]
+struct PropertyPayload<'a> {
+    server: &'a str,
+
+    /// The property as `changed`, like `motd`.
+    changed: Property,
+    old: &'a str,
+    new: &'a str,
+
+    /// When the change was detected, in seconds since the Unix
+    /// epoch.
+    checked_at: u64,
+
+    /// The text of the notification.
+    message: &'a str,
+}
+
+impl<'a> WebhookPayload<'a> {
+    fn new(change: &'a StatusChange, message: &'a str) -> WebhookPayload<'a> {
+        let previous = change.previous_duration.as_secs();
+        let status = change.ping.as_ref().map(|x| &x.status);
+        WebhookPayload {
+            server: &change.server,
+            online: change.online(),
+            checked_at: change.checked_at.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs()),
+            downtime: change.online().then_some(previous),
+            uptime: (!change.online()).then_some(previous),
+            players_online: status.map(|x| x.players.online),
+            players_max: status.map(|x| x.players.max),
+            version: status.map(|x| x.version.name.as_str()),
+            motd: status.map(|x| x.motd.as_str()),
+            message,
+        }
+    }
+}
+
+/// Sends the notifications of status changes to all
+/// configured receivers.
+pub struct Notifier {
+    client: reqwest::Client,
+    webhooks: Vec<String>,
+    telegram: Option<TelegramConfig>,
+    groups: HashMap<String, NotifyConfig>,
+    templates: TemplateConfig,
+}
+
+impl Notifier {
+    pub fn new(config: &MonitorConfig) -> Notifier {
+        Notifier {
+            client: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            webhooks: config.webhooks.clone(),
+            telegram: config.telegram.clone(),
+            groups: config.groups.clone(),
+            templates: config.templates.clone(),
+        }
+    }
+
+    /// Notifies all receivers of `change` of `server` at once,
+    /// logging the ones that fail.
+    pub async fn notify(&self, server: &MonitoredServer, change: &StatusChange) {
+        let message = change.message(&self.templates);
+        let payload = WebhookPayload::new(change, &message);
+        self.send(
+            server,
+            &payload,
+            &message,
+            |url| discord::send(&self.client, url, change, &message).boxed(),
+            |url| slack::send(&self.client, url, change, &message).boxed(),
+        ).await;
+    }
+
+    /// Notifies all receivers of `change` of a watched property
+    /// of `server` at once, logging the ones that fail.
+    pub async fn notify_property(&self, server: &MonitoredServer, change: &PropertyChange) {
+        let message = change.message(&self.templates);
+        let payload = PropertyPayload {
+            server: &change.server,
+            changed: change.property,
+            old: &change.old,
+            new: &change.new,
+            checked_at: change.checked_at.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs()),
+            message: &message,
+        };
+        self.send(
+            server,
+            &payload,
+            &message,
+            |url| discord::send_text(&self.client, url, &message).boxed(),
+            |url| slack::send_text(&self.client, url, &message).boxed(),
+        ).await;
+    }
+
+    /// POSTs `payload` to the webhooks, sends to Discord and
+    /// Slack what `discord` and `slack` send to a URL, and
+    /// `message` to Telegram.
+    async fn send<'a, D, S>(&'a self, server: &'a MonitoredServer, payload: &'a (impl Serialize + Sync), message: &'a str, discord: D, slack: S)
+    where
+        D: Fn(&'a str) -> BoxFuture<'a, Result<(), reqwest::Error>>,
+        S: Fn(&'a str) -> BoxFuture<'a, Result<(), reqwest::Error>>,
+    {
+        let group = server.group.as_ref().and_then(|x| self.groups.get(x));
+        let receivers = || [Some(&server.notify), group].into_iter().flatten();
+        let webhooks = self.webhooks.iter().chain(receivers().flat_map(|x| &x.webhooks)).map(|url| async move {
+            let result = self.client.post(url).json(payload).send().await
+                .and_then(|x| x.error_for_status());
+            if let Err(e) = result {
+                warn!("can't notify {} of {}: {}", url, server.address, e);
+            }
+        });
+        let discord = receivers().flat_map(|x| &x.discord).map(|url| {
+            let send = discord(url.as_str());
+            async move {
+                // Errors would contain the URL with the webhook token
+                if let Err(e) = send.await {
+                    warn!("can't notify Discord of {}: {}", server.address, e.without_url());
+                }
+            }
+        });
+        let telegram = self.telegram.iter().map(|config| async move {
+            if let Err(e) = telegram::send(&self.client, config, message).await {
+                warn!("can't notify Telegram of {}: {}", server.address, e);
+            }
+        });
+        let slack = receivers().flat_map(|x| &x.slack).map(|url| {
+            let send = slack(url.as_str());
+            async move {
+                if let Err(e) = send.await {
+                    warn!("can't notify Slack of {}: {}", server.address, e.without_url());
+                }
+            }
+        });
+        join_all(webhooks.map(FutureExt::boxed)
+            .chain(discord.map(FutureExt::boxed))
+            .chain(slack.map(FutureExt::boxed))
+            .chain(telegram.map(FutureExt::boxed))).await;
+    }
+}
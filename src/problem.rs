@@ -0,0 +1,113 @@
+//! Errors as `application/problem+json` of RFC 7807, for
+//! clients asking for it with the `Accept` header.
+
+use rocket::{Request, Response};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, Responder};
+use rocket::serde::json::{serde_json, Value};
+use serde::Serialize;
+use crate::error::StatusError;
+
+/// The content type of problems.
+pub fn content_type() -> ContentType {
+    ContentType::new("application", "problem+json")
+}
+
+/// The HTTP status of a problem with `err`.
+fn status(err: &StatusError) -> Status {
+    match err {
+        StatusError::InvalidInput | StatusError::InvalidConfig => Status::BadRequest,
+        StatusError::Unauthorized => Status::Unauthorized,
+        StatusError::Forbidden => Status::Forbidden,
+        StatusError::NotMonitored | StatusError::UnknownTag | StatusError::NotFound => Status::NotFound,
+        StatusError::AlreadyMonitored | StatusError::Configured => Status::Conflict,
+        StatusError::TooLarge => Status::PayloadTooLarge,
+        StatusError::RateLimited => Status::TooManyRequests,
+        StatusError::HistoryError | StatusError::InternalError => Status::InternalServerError,
+        StatusError::ProtocolError | StatusError::MalformedJson | StatusError::UnexpectedPacket | StatusError::ConnectionReset | StatusError::UnexpectedVersion => Status::BadGateway,
+        StatusError::UnknownHost | StatusError::DnsError => Status::BadGateway,
+        StatusError::ConnectionRefused | StatusError::Unreachable => Status::ServiceUnavailable,
+        StatusError::Timeout | StatusError::ConnectTimeout | StatusError::ReadTimeout => Status::GatewayTimeout,
+    }
+}
+
+/// A problem detail of RFC 7807.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    /// Identifies the kind of problem, like
+    /// `urn:mcstatus-checker:error:Timeout`.
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    pub title: String,
+    pub status: u16,
+
+    /// What went wrong with this request.
+    pub detail: String,
+
+    /// The URI of the request.
+    pub instance: String,
+
+    /// The more specific cause of the error, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cause: Option<String>,
+}
+
+impl Problem {
+    /// The problem of a response serialized with structured
+    /// errors, if it has an `err`.
+    pub fn of(response: &Value, request: &Request<'_>) -> Option<Problem> {
+        let name = response.pointer("/err/code")?.as_str()?;
+        let err: StatusError = serde_json::from_value(Value::from(name)).ok()?;
+        let cause = response.pointer("/err/cause").and_then(Value::as_str);
+        // The cause tells a server that is offline from one
+        // whose domain doesn't exist, for example
+        let specific = cause.and_then(|x| serde_json::from_value(Value::from(x)).ok()).unwrap_or_else(|| err.clone());
+        let message = response.pointer("/err/message").and_then(Value::as_str).unwrap_or_default();
+        let detail = match (response.pointer("/target/host").and_then(Value::as_str), response.pointer("/target/port").and_then(Value::as_u64)) {
+            (Some(host), Some(port)) => format!("{} for {}:{}", message, host, port),
+            _ => message.to_string(),
+        };
+        Some(Problem {
+            kind: format!("urn:mcstatus-checker:error:{}", name),
+            title: err.to_string(),
+            status: status(&specific).code,
+            detail,
+            instance: request.uri().to_string(),
+            cause: cause.map(str::to_string),
+        })
+    }
+}
+
+/// The status of the problem responded, which the status of a
+/// route wrapping the response would replace otherwise.
+struct ProblemStatus(Option<Status>);
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for Problem {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        let status = Status::from_code(self.status).unwrap_or(Status::InternalServerError);
+        request.local_cache(|| ProblemStatus(Some(status)));
+        let body = serde_json::to_vec(&self).map_err(|_| Status::InternalServerError)?;
+        (status, (content_type(), body)).respond_to(request)
+    }
+}
+
+/// Restores the status of problems.
+pub struct ProblemStatuses;
+
+#[rocket::async_trait]
+impl Fairing for ProblemStatuses {
+    fn info(&self) -> Info {
+        Info {
+            name: "Problem statuses",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if let ProblemStatus(Some(status)) = request.local_cache(|| ProblemStatus(None)) {
+            response.set_status(*status);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Connecting to servers through a SOCKS5 proxy, for servers
+//! only reachable from specific networks.
+
+use serde::Deserialize;
+use tokio::net::TcpStream;
+use tokio_socks::tcp::Socks5Stream;
+use crate::PingError;
+
+/// A SOCKS5 proxy, configured as `socks5://host:port` with
+/// optional `user:password@` credentials.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Proxy {
+    /// The `host:port` of the proxy.
+    pub address: String,
+
+    /// The username and password to authenticate with.
+    pub credentials: Option<(String, String)>,
+}
+
+impl TryFrom<String> for Proxy {
+    type Error = String;
+
+    fn try_from(url: String) -> Result<Self, Self::Error> {
+        let rest = url.strip_prefix("socks5://").unwrap_or(&url);
+        let (credentials, address) = match rest.rsplit_once('@') {
+            Some((credentials, address)) => {
+                let (user, password) = credentials.split_once(':').unwrap_or((credentials, ""));
+                (Some((user.to_string(), password.to_string())), address)
+            }
+            None => (None, rest),
+        };
+        if address.rsplit_once(':').is_none_or(|(_, port)| port.parse::<u16>().is_err()) {
+            return Err(format!("invalid SOCKS5 proxy `{}`", url));
+        }
+
+        Ok(Proxy { address: address.to_string(), credentials })
+    }
+}
+
+/// Opens a TCP connection to `host:port`, through `proxy` if
+/// there is one. Hostnames are resolved by the proxy.
+pub async fn connect(host: &str, port: u16, proxy: Option<&Proxy>) -> Result<TcpStream, PingError> {
+    let proxy = match proxy {
+        Some(x) => x,
+        None => return TcpStream::connect((host, port)).await.map_err(|e| PingError::io(&e)),
+    };
+
+    let stream = match &proxy.credentials {
+        Some((user, password)) => Socks5Stream::connect_with_password(proxy.address.as_str(), (host, port), user, password).await,
+        None => Socks5Stream::connect(proxy.address.as_str(), (host, port)).await,
+    };
+    stream.map(|x| x.into_inner()).map_err(|e| socks_error(&e))
+}
+
+/// The error of a failed connection through the proxy, as
+/// the proxy reports it.
+fn socks_error(e: &tokio_socks::Error) -> PingError {
+    match e {
+        tokio_socks::Error::Io(e) => PingError::io(e),
+        tokio_socks::Error::ConnectionRefused => PingError::ConnectionRefused,
+        tokio_socks::Error::HostUnreachable | tokio_socks::Error::NetworkUnreachable => PingError::Unreachable,
+        _ => PingError::ProtocolError,
+    }
+}
@@ -0,0 +1,299 @@
+//! The GameSpy4 based [Query](https://wiki.vg/Query) protocol,
+//! answered by servers with `enable-query=true`.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use tokio::net::{lookup_host, UdpSocket};
+use crate::PingError;
+
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+const TYPE_HANDSHAKE: u8 = 9;
+const TYPE_STAT: u8 = 0;
+
+/// The reply to a basic stat request.
+#[derive(Debug, Serialize)]
+pub struct BasicStat {
+    /// The server's MOTD.
+    pub motd: String,
+
+    /// The game type, always "SMP".
+    pub game_type: String,
+
+    /// The name of the default world.
+    pub map: String,
+
+    /// The number of players currently online.
+    pub online: u32,
+
+    /// The configured maximum number of players for the
+    /// server.
+    pub max: u32,
+
+    /// The port the server listens on.
+    pub host_port: u16,
+
+    /// The IP the server listens on.
+    pub host_ip: String,
+}
+
+/// The reply to a full stat request.
+#[derive(Debug, Serialize)]
+pub struct FullStat {
+    /// The server's MOTD.
+    pub motd: String,
+
+    /// The game type, always "SMP".
+    pub game_type: String,
+
+    /// The game ID, always "MINECRAFT".
+    pub game_id: String,
+
+    /// The server's Minecraft version, i.e. "1.15.2".
+    pub version: String,
+
+    /// The server software reported in front of the plugin
+    /// list, i.e. "CraftBukkit on Bukkit 1.2.5-R4.0".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_mod: Option<String>,
+
+    /// The installed plugins with their versions.
+    pub plugins: Vec<String>,
+
+    /// The name of the default world.
+    pub map: String,
+
+    /// The number of players currently online.
+    pub online: u32,
+
+    /// The configured maximum number of players for the
+    /// server.
+    pub max: u32,
+
+    /// The port the server listens on.
+    pub host_port: u16,
+
+    /// The IP the server listens on.
+    pub host_ip: String,
+
+    /// The names of all online players.
+    pub players: Vec<String>,
+}
+
+/// Either form of stat the server replied with.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum QueryStatus {
+    Basic(BasicStat),
+    Full(FullStat),
+}
+
+/// Queries `host:port`, asking for the full stat unless
+/// `basic` is set, waiting up to `timeout` for every try.
+#[tracing::instrument(name = "query", skip(timeout))]
+pub async fn query(host: &str, port: u16, basic: bool, timeout: Duration, retry: usize) -> Result<QueryStatus, PingError> {
+    let mut last_err = PingError::ProtocolError;
+    for _ in 0..retry {
+        match tokio::time::timeout(timeout, query_once(host, port, basic)).await {
+            Ok(Ok(x)) => {
+                return Ok(x);
+            }
+            Ok(Err(e)) => {
+                last_err = e;
+            }
+            Err(_) => {
+                last_err = PingError::Timeout;
+            }
+        }
+    }
+    Err(last_err)
+}
+
+async fn query_once(host: &str, port: u16, basic: bool) -> Result<QueryStatus, PingError> {
+    let addr = lookup_host((host, port)).await
+        .map_err(|_| PingError::ProtocolError)?
+        .next()
+        .ok_or(PingError::ProtocolError)?;
+    let bind = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(bind).await.map_err(|_| PingError::ProtocolError)?;
+    socket.connect(addr).await.map_err(|e| PingError::io(&e))?;
+
+    let session = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().subsec_nanos() as i32 & 0x0F0F_0F0F;
+
+    let response = request(&socket, TYPE_HANDSHAKE, session, &[]).await?;
+    let token: i32 = read_string(&mut response.as_slice())
+        .and_then(|x| x.parse().ok())
+        .ok_or(PingError::ProtocolError)?;
+
+    let mut payload = token.to_be_bytes().to_vec();
+    if basic {
+        let response = request(&socket, TYPE_STAT, session, &payload).await?;
+        parse_basic(&response).map(QueryStatus::Basic)
+    } else {
+        payload.extend_from_slice(&[0; 4]);
+        let response = request(&socket, TYPE_STAT, session, &payload).await?;
+        parse_full(&response).map(QueryStatus::Full)
+    }
+}
+
+/// Sends a request and returns the payload of the reply
+/// after checking its type and session ID.
+async fn request(socket: &UdpSocket, kind: u8, session: i32, payload: &[u8]) -> Result<Vec<u8>, PingError> {
+    let mut packet = MAGIC.to_vec();
+    packet.push(kind);
+    packet.extend_from_slice(&session.to_be_bytes());
+    packet.extend_from_slice(payload);
+    socket.send(&packet).await.map_err(|e| PingError::io(&e))?;
+
+    let mut buffer = [0u8; 4096];
+    let len = socket.recv(&mut buffer).await.map_err(|e| PingError::io(&e))?;
+    if len < 5 || buffer[0] != kind || buffer[1..5] != session.to_be_bytes() {
+        return Err(PingError::ProtocolError);
+    }
+    Ok(buffer[5..len].to_vec())
+}
+
+/// Reads a null-terminated string, advancing `buffer` past
+/// the terminator.
+fn read_string(buffer: &mut &[u8]) -> Option<String> {
+    let end = buffer.iter().position(|x| *x == 0)?;
+    let string = String::from_utf8_lossy(&buffer[..end]).into_owned();
+    *buffer = &buffer[end + 1..];
+    Some(string)
+}
+
+fn parse_basic(mut buffer: &[u8]) -> Result<BasicStat, PingError> {
+    let mut next = || read_string(&mut buffer).ok_or(PingError::ProtocolError);
+    let motd = next()?;
+    let game_type = next()?;
+    let map = next()?;
+    let online = next()?.parse().map_err(|_| PingError::ProtocolError)?;
+    let max = next()?.parse().map_err(|_| PingError::ProtocolError)?;
+
+    if buffer.len() < 2 {
+        return Err(PingError::ProtocolError);
+    }
+    // The only little-endian field of the protocol
+    let host_port = u16::from_le_bytes([buffer[0], buffer[1]]);
+    buffer = &buffer[2..];
+    let host_ip = read_string(&mut buffer).ok_or(PingError::ProtocolError)?;
+
+    Ok(BasicStat { motd, game_type, map, online, max, host_port, host_ip })
+}
+
+/// Parses the full stat: padding, the key/value section
+/// ending with an empty key, more padding and the player
+/// names ending with an empty name.
+fn parse_full(buffer: &[u8]) -> Result<FullStat, PingError> {
+    let mut buffer = buffer.get(11..).ok_or(PingError::ProtocolError)?;
+
+    let mut values = std::collections::HashMap::new();
+    loop {
+        let key = read_string(&mut buffer).ok_or(PingError::ProtocolError)?;
+        if key.is_empty() {
+            break;
+        }
+        let value = read_string(&mut buffer).ok_or(PingError::ProtocolError)?;
+        values.insert(key, value);
+    }
+
+    let mut buffer = buffer.get(10..).ok_or(PingError::ProtocolError)?;
+    let mut players = Vec::new();
+    while let Some(name) = read_string(&mut buffer) {
+        if name.is_empty() {
+            break;
+        }
+        players.push(name);
+    }
+
+    let mut value = |key: &str| values.remove(key).unwrap_or_default();
+    let (server_mod, plugins) = parse_plugins(&value("plugins"));
+
+    Ok(FullStat {
+        motd: value("hostname"),
+        game_type: value("gametype"),
+        game_id: value("game_id"),
+        version: value("version"),
+        server_mod,
+        plugins,
+        map: value("map"),
+        online: value("numplayers").parse().map_err(|_| PingError::ProtocolError)?,
+        max: value("maxplayers").parse().map_err(|_| PingError::ProtocolError)?,
+        host_port: value("hostport").parse().map_err(|_| PingError::ProtocolError)?,
+        host_ip: value("hostip"),
+        players,
+    })
+}
+
+/// Splits `server mod: plugin 1; plugin 2` into its parts.
+/// Vanilla servers send an empty string, servers without
+/// plugins only the server mod.
+fn parse_plugins(plugins: &str) -> (Option<String>, Vec<String>) {
+    let (server_mod, list) = plugins.split_once(':').unwrap_or((plugins, ""));
+    let server_mod = Some(server_mod.trim().to_string());
+    let list = list.split(';')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect();
+    (server_mod.filter(|x| !x.is_empty()), list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASIC: &[u8] = b"A Minecraft Server\0SMP\0world\x002\x0020\0\xdd\x63127.0.0.1\0";
+
+    fn full() -> Vec<u8> {
+        let mut buffer = b"splitnum\0\x80\0".to_vec();
+        buffer.extend_from_slice(b"hostname\0A Minecraft Server\0gametype\0SMP\0game_id\0MINECRAFT\0version\x001.20.1\0");
+        buffer.extend_from_slice(b"plugins\0Paper on 1.20.1: WorldEdit 7.2; Essentials\0map\0world\0");
+        buffer.extend_from_slice(b"numplayers\x002\0maxplayers\x0020\0hostport\x0025565\0hostip\x00127.0.0.1\0\0");
+        buffer.extend_from_slice(b"\x01player_\0\0");
+        buffer.extend_from_slice(b"Alice\0Bob\0\0");
+        buffer
+    }
+
+    #[test]
+    fn parses_basic_stats() {
+        let stat = parse_basic(BASIC).unwrap();
+        assert_eq!(stat.motd, "A Minecraft Server");
+        assert_eq!((stat.online, stat.max), (2, 20));
+        assert_eq!(stat.host_port, 25565);
+        assert_eq!(stat.host_ip, "127.0.0.1");
+    }
+
+    #[test]
+    fn refuses_truncated_basic_stats() {
+        for len in [0, 19, 30, BASIC.len() - 11, BASIC.len() - 10, BASIC.len() - 1] {
+            assert!(matches!(parse_basic(&BASIC[..len]), Err(PingError::ProtocolError)), "{}", len);
+        }
+    }
+
+    #[test]
+    fn parses_full_stats() {
+        let stat = parse_full(&full()).unwrap();
+        assert_eq!(stat.motd, "A Minecraft Server");
+        assert_eq!(stat.version, "1.20.1");
+        assert_eq!(stat.server_mod.as_deref(), Some("Paper on 1.20.1"));
+        assert_eq!(stat.plugins, ["WorldEdit 7.2", "Essentials"]);
+        assert_eq!((stat.online, stat.max, stat.host_port), (2, 20, 25565));
+        assert_eq!(stat.players, ["Alice", "Bob"]);
+    }
+
+    #[test]
+    fn refuses_truncated_full_stats() {
+        let buffer = full();
+        let players = buffer.len() - b"\x01player_\0\0Alice\0Bob\0\0".len();
+        for len in [0, 10, 20, 60, players - 1, players + 5] {
+            assert!(matches!(parse_full(&buffer[..len]), Err(PingError::ProtocolError)), "{}", len);
+        }
+    }
+
+    #[test]
+    fn splits_plugins() {
+        assert_eq!(parse_plugins(""), (None, vec![]));
+        assert_eq!(parse_plugins("CraftBukkit"), (Some("CraftBukkit".to_string()), vec![]));
+        assert_eq!(parse_plugins("Paper: A; B;"), (Some("Paper".to_string()), vec!["A".to_string(), "B".to_string()]));
+    }
+}
@@ -0,0 +1,215 @@
+//! Token bucket rate limits, so a public instance can't be
+//! abused to flood servers with pings.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use rocket::Response;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use crate::auth::{ApiKey, ApiKeys};
+
+/// The number of buckets above which full ones are dropped.
+const MAX_BUCKETS: usize = 10000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// The buckets of a limiter with the limits they are filled
+/// by, which may change while running.
+struct Buckets<K> {
+    buckets: HashMap<K, Bucket>,
+    rate: f64,
+    burst: f64,
+}
+
+/// A token bucket for every key, refilled at a constant rate
+/// up to the burst size. Clones share their buckets.
+#[derive(Clone)]
+pub struct RateLimiter<K> {
+    state: Arc<Mutex<Buckets<K>>>,
+}
+
+/// The state of the bucket of a key, for clients to throttle
+/// themselves.
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    /// The requests allowed at once.
+    pub limit: u32,
+
+    /// The requests allowed right now.
+    pub remaining: u32,
+
+    /// The time until the bucket is full again.
+    pub reset: Duration,
+}
+
+/// Limits how often every resolved host and port is pinged.
+pub type TargetLimiter = RateLimiter<(String, u16)>;
+
+/// The tokens per second of `per_minute`.
+fn per_second(per_minute: u32) -> f64 {
+    per_minute as f64 / 60.0
+}
+
+/// The tokens per second of one every `interval`.
+fn every(interval: Duration) -> f64 {
+    if interval.is_zero() { 0.0 } else { 1.0 / interval.as_secs_f64() }
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    fn with_rate(rate: f64, burst: f64) -> RateLimiter<K> {
+        RateLimiter {
+            state: Arc::new(Mutex::new(Buckets { buckets: HashMap::new(), rate, burst })),
+        }
+    }
+
+    /// Allows `per_minute` requests per key on average and up
+    /// to `burst` at once. A rate of 0 disables the limit.
+    pub fn new(per_minute: u32, burst: u32) -> RateLimiter<K> {
+        RateLimiter::with_rate(per_second(per_minute), burst.max(1) as f64)
+    }
+
+    /// Allows one request per key every `interval`. An
+    /// interval of 0 disables the limit.
+    pub fn with_interval(interval: Duration) -> RateLimiter<K> {
+        RateLimiter::with_rate(every(interval), 1.0)
+    }
+
+    /// Changes the limits like [`RateLimiter::new`], keeping
+    /// the tokens of every key.
+    pub fn set_limits(&self, per_minute: u32, burst: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.rate = per_second(per_minute);
+        state.burst = burst.max(1) as f64;
+    }
+
+    /// Changes the limit like [`RateLimiter::with_interval`].
+    pub fn set_interval(&self, interval: Duration) {
+        self.state.lock().unwrap().rate = every(interval);
+    }
+
+    /// Takes a token for `key`, or returns how long it takes
+    /// until there is one again.
+    pub fn take(&self, key: K) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        let (rate, burst) = (state.rate, state.burst);
+        if rate == 0.0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let buckets = &mut state.buckets;
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|_, x| x.tokens + now.duration_since(x.updated).as_secs_f64() * rate < burst);
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket { tokens: burst, updated: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+
+    /// The quota of `key` without taking a token, none if the
+    /// limit is disabled.
+    pub fn quota(&self, key: &K) -> Option<Quota> {
+        let state = self.state.lock().unwrap();
+        if state.rate == 0.0 {
+            return None;
+        }
+        let tokens = state.buckets.get(key).map_or(state.burst, |x| {
+            (x.tokens + x.updated.elapsed().as_secs_f64() * state.rate).min(state.burst)
+        });
+        Some(Quota {
+            limit: state.burst as u32,
+            remaining: tokens.floor() as u32,
+            reset: Duration::from_secs_f64((state.burst - tokens) / state.rate),
+        })
+    }
+}
+
+/// A request with a valid API key, if they are required,
+/// within the rate limit of its client IP or key, answered
+/// with 401 Unauthorized or 429 Too Many Requests otherwise.
+/// Requests with a valid API key are limited per key
+/// instead of per client IP.
+pub struct ClientLimit;
+
+/// The time until the next request is allowed, kept for the
+/// catcher of 429 Too Many Requests.
+pub struct RetryAfter(pub Option<Duration>);
+
+impl RetryAfter {
+    /// The `Retry-After` header of `wait`, rounded up to whole
+    /// seconds and at least one, so clients don't retry early.
+    pub fn header(wait: Duration) -> Header<'static> {
+        Header::new("Retry-After", (wait.as_secs_f64().ceil() as u64).max(1).to_string())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientLimit {
+    /// The time until the next request is allowed if over the
+    /// limit, none if the API key is missing or invalid.
+    type Error = Option<Duration>;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if let Outcome::Error(e) = request.guard::<ApiKey>().await {
+            return Outcome::Error(e);
+        }
+
+        let (limiter, ip) = match (request.rocket().state::<RateLimiter<IpAddr>>(), request.client_ip()) {
+            (Some(limiter), Some(ip)) => (limiter, ip),
+            _ => return Outcome::Success(ClientLimit),
+        };
+        if request.rocket().state::<ApiKeys>().and_then(|x| x.find(request)).is_some() {
+            return Outcome::Success(ClientLimit);
+        }
+
+        match limiter.take(ip) {
+            Ok(()) => Outcome::Success(ClientLimit),
+            Err(retry_after) => {
+                request.local_cache(|| RetryAfter(Some(retry_after)));
+                Outcome::Error((Status::TooManyRequests, Some(retry_after)))
+            }
+        }
+    }
+}
+
+/// Tells clients their quota with `X-RateLimit-Limit`,
+/// `X-RateLimit-Remaining` and `X-RateLimit-Reset` on every
+/// response, that of their API key if they sent one.
+pub struct RateLimitHeaders;
+
+#[rocket::async_trait]
+impl Fairing for RateLimitHeaders {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate limit headers",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let rocket = request.rocket();
+        let quota = match rocket.state::<ApiKeys>().and_then(|x| x.find(request)) {
+            Some(limiter) => limiter.quota(&()),
+            None => rocket.state::<RateLimiter<IpAddr>>().zip(request.client_ip()).and_then(|(limiter, ip)| limiter.quota(&ip)),
+        };
+        if let Some(quota) = quota {
+            response.set_header(Header::new("X-RateLimit-Limit", quota.limit.to_string()));
+            response.set_header(Header::new("X-RateLimit-Remaining", quota.remaining.to_string()));
+            response.set_header(Header::new("X-RateLimit-Reset", (quota.reset.as_secs_f64().ceil() as u64).to_string()));
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Reporting panics, unparsable status responses and storage
+//! errors to Sentry, so maintainers of public instances see
+//! what breaks in the wild.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use sentry::{ClientInitGuard, ClientOptions, Level};
+use crate::config::SentryConfig;
+
+/// The most bytes of a status response attached to a report.
+const MAX_PAYLOAD: usize = 16 * 1024;
+
+/// Whether status responses are left out of the reports, as
+/// by default until the configuration is read.
+static REDACT_PAYLOADS: AtomicBool = AtomicBool::new(true);
+
+/// Starts reporting to the DSN of `config`, including panics.
+/// Reports are sent until the returned guard is dropped,
+/// which waits for the pending ones.
+pub fn init(config: &SentryConfig) -> ClientInitGuard {
+    REDACT_PAYLOADS.store(config.redact_payloads, Ordering::Relaxed);
+    sentry::init((config.dsn.as_str(), ClientOptions {
+        release: sentry::release_name!(),
+        environment: config.environment.clone().map(Into::into),
+        sample_rate: config.sample_rate,
+        ..Default::default()
+    }))
+}
+
+fn enabled() -> bool {
+    sentry::Hub::current().client().is_some_and(|x| x.is_enabled())
+}
+
+/// Reports that the status response of `server` couldn't be
+/// parsed, with the `payload` unless it is redacted.
+pub fn protocol_error(server: &str, payload: &str) {
+    if !enabled() {
+        return;
+    }
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("server", server);
+            if !REDACT_PAYLOADS.load(Ordering::Relaxed) {
+                let mut end = payload.len().min(MAX_PAYLOAD);
+                while !payload.is_char_boundary(end) {
+                    end -= 1;
+                }
+                scope.set_extra("payload", payload[..end].into());
+            }
+        },
+        || sentry::capture_message("Unparsable status response", Level::Warning),
+    );
+}
+
+/// Reports that the history couldn't be read or written, for
+/// `server` if it was about one.
+pub fn storage_error(server: Option<&str>, e: &sqlx::Error) {
+    if !enabled() {
+        return;
+    }
+    sentry::with_scope(
+        |scope| {
+            if let Some(server) = server {
+                scope.set_tag("server", server);
+            }
+        },
+        || sentry::capture_error(e),
+    );
+}
@@ -0,0 +1,43 @@
+//! Headers keeping browsers from misusing the responses, as
+//! the badges and banners are embedded on other sites.
+
+use rocket::{Request, Response};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Header};
+use crate::config::Config;
+
+/// Sets the configured security headers, unless the route
+/// set them itself.
+pub struct SecurityHeaders;
+
+#[rocket::async_trait]
+impl Fairing for SecurityHeaders {
+    fn info(&self) -> Info {
+        Info {
+            name: "Security headers",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let config = match request.rocket().state::<Config>() {
+            Some(config) => &config.security_headers,
+            None => return,
+        };
+        // Only documents are affected by a policy for content
+        let document = response.content_type().is_some_and(|x| x == ContentType::HTML || x == ContentType::SVG);
+        let tls = request.rocket().config().tls_enabled();
+        let headers = [
+            ("X-Content-Type-Options", &config.content_type_options, true),
+            ("Referrer-Policy", &config.referrer_policy, true),
+            ("Content-Security-Policy", &config.content_security_policy, document),
+            ("Cross-Origin-Resource-Policy", &config.cross_origin_resource_policy, true),
+            ("Strict-Transport-Security", &config.strict_transport_security, tls),
+        ];
+        for (name, value, applies) in headers {
+            if applies && !value.is_empty() && !response.headers().contains(name) {
+                response.set_header(Header::new(name, value.clone()));
+            }
+        }
+    }
+}
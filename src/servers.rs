@@ -0,0 +1,284 @@
+//! The monitored servers, the configured ones and those added
+//! through the admin routes, which are kept in the history.
+
+use std::sync::Mutex;
+use rocket::serde::json::serde_json;
+use serde::{Deserialize, Serialize};
+use mcstatus_checker::cache;
+use mcstatus_checker::parse_address;
+use crate::error::StatusError;
+use crate::config::{MonitorConfig, MonitoredServer};
+use crate::cron::Schedule;
+use crate::history::History;
+use crate::report;
+
+/// Where a monitored server comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// The configuration, which is only changed by reloading
+    /// it.
+    Config,
+
+    /// The admin routes.
+    Admin,
+}
+
+/// A monitored server and where it comes from.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    #[serde(flatten)]
+    pub server: MonitoredServer,
+
+    pub source: Source,
+}
+
+/// A server that couldn't be imported and why.
+#[derive(Debug, Serialize)]
+pub struct SkippedServer {
+    pub address: String,
+    pub err: StatusError,
+}
+
+/// A monitored server as a line of CSV, with the settings
+/// that fit into one.
+#[derive(Debug, Deserialize, Serialize)]
+struct CsvServer {
+    address: String,
+    interval: Option<u64>,
+    schedule: Option<Schedule>,
+    timeout_ms: Option<u64>,
+    protocol: Option<i32>,
+    expected_version: Option<String>,
+
+    /// The tags separated by `;`.
+    #[serde(default)]
+    tags: String,
+
+    group: Option<String>,
+    down_after: Option<u32>,
+    up_after: Option<u32>,
+}
+
+impl From<CsvServer> for MonitoredServer {
+    fn from(server: CsvServer) -> Self {
+        MonitoredServer {
+            address: server.address,
+            interval: server.interval,
+            schedule: server.schedule,
+            timeout_ms: server.timeout_ms,
+            protocol: server.protocol,
+            expected_version: server.expected_version.filter(|x| !x.is_empty()),
+            tags: server.tags.split(';').map(str::trim).filter(|x| !x.is_empty()).map(str::to_string).collect(),
+            group: server.group.filter(|x| !x.is_empty()),
+            down_after: server.down_after,
+            up_after: server.up_after,
+            maintenance: Vec::new(),
+            notify: Default::default(),
+        }
+    }
+}
+
+/// Reads servers from CSV with a header line naming the
+/// columns, of which only `address` is required.
+pub fn from_csv(text: &str) -> Result<Vec<MonitoredServer>, csv::Error> {
+    csv::Reader::from_reader(text.as_bytes())
+        .deserialize::<CsvServer>()
+        .map(|x| x.map(MonitoredServer::from))
+        .collect()
+}
+
+/// Writes `servers` as CSV with a header line, leaving out
+/// their maintenance windows and receivers.
+pub fn to_csv(servers: &[MonitoredServer]) -> Result<String, csv::Error> {
+    // The header is written by hand so there is one without
+    // any servers
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+    writer.write_record(["address", "interval", "schedule", "timeout_ms", "protocol", "expected_version", "tags", "group", "down_after", "up_after"])?;
+    for server in servers {
+        writer.serialize(CsvServer {
+            address: server.address.clone(),
+            interval: server.interval,
+            schedule: server.schedule.clone(),
+            timeout_ms: server.timeout_ms,
+            protocol: server.protocol,
+            expected_version: server.expected_version.clone(),
+            tags: server.tags.join(";"),
+            group: server.group.clone(),
+            down_after: server.down_after,
+            up_after: server.up_after,
+        })?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8(bytes).unwrap_or_default())
+}
+
+/// All monitored servers, which the monitor checks and the
+/// history routes respond about.
+pub struct MonitoredServers {
+    entries: Mutex<Vec<Entry>>,
+
+    /// Makes changes wait for each other while they are
+    /// stored.
+    changes: tokio::sync::Mutex<()>,
+
+    history: Option<History>,
+}
+
+/// Whether `address` refers to the host and port, with hosts
+/// compared like in the cache key.
+fn same_address(address: &str, host: &str, port: Option<u16>) -> bool {
+    parse_address(address, None).is_ok_and(|(h, p)| cache::normalize_host(h) == host && p == port)
+}
+
+/// Logs a failure to store the monitored servers.
+fn store_error(address: &str, e: &sqlx::Error) -> StatusError {
+    error!("can't store the monitored server {}: {}", address, e);
+    report::storage_error(Some(address), e);
+    StatusError::HistoryError
+}
+
+impl MonitoredServers {
+    /// The servers of `config` followed by the ones stored in
+    /// `history`, skipping the stored ones that are configured
+    /// too or can't be read anymore.
+    pub async fn load(config: &MonitorConfig, history: Option<History>) -> Result<MonitoredServers, sqlx::Error> {
+        let mut entries: Vec<Entry> = config.servers.iter()
+            .map(|x| Entry { server: x.clone(), source: Source::Config })
+            .collect();
+        if let Some(history) = &history {
+            for stored in history.servers().await? {
+                match serde_json::from_str::<MonitoredServer>(&stored) {
+                    Ok(server) if entries.iter().any(|x| x.server.address == server.address) => {
+                        warn!("ignoring the stored monitored server {} as it is configured", server.address);
+                    }
+                    Ok(server) => entries.push(Entry { server, source: Source::Admin }),
+                    Err(e) => warn!("ignoring a stored monitored server: {}", e),
+                }
+            }
+        }
+        Ok(MonitoredServers { entries: Mutex::new(entries), changes: tokio::sync::Mutex::new(()), history })
+    }
+
+    /// Replaces the configured servers with the ones of
+    /// `config`, dropping the ones added at runtime that are
+    /// configured now.
+    pub async fn reload(&self, config: &MonitorConfig) {
+        let _changes = self.changes.lock().await;
+        let mut entries = self.entries.lock().unwrap();
+        let added = entries.drain(..)
+            .filter(|x| x.source == Source::Admin)
+            .filter(|x| !config.servers.iter().any(|server| server.address == x.server.address));
+        let mut reloaded: Vec<Entry> = config.servers.iter()
+            .map(|x| Entry { server: x.clone(), source: Source::Config })
+            .collect();
+        reloaded.extend(added);
+        *entries = reloaded;
+    }
+
+    /// All monitored servers, the configured ones first.
+    pub fn list(&self) -> Vec<Entry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// The servers to check.
+    pub fn servers(&self) -> Vec<MonitoredServer> {
+        self.entries.lock().unwrap().iter().map(|x| x.server.clone()).collect()
+    }
+
+    /// The servers with `tag`.
+    pub fn tagged(&self, tag: &str) -> Vec<MonitoredServer> {
+        self.entries.lock().unwrap().iter()
+            .filter(|x| x.server.tags.iter().any(|x| x == tag))
+            .map(|x| x.server.clone())
+            .collect()
+    }
+
+    /// The monitored server that `address` with the `port`
+    /// query parameter refers to.
+    pub fn find(&self, address: &str, port: Option<u16>) -> Result<Entry, StatusError> {
+        let (host, port) = parse_address(address, port)?;
+        let host = cache::normalize_host(host);
+        self.entries.lock().unwrap().iter()
+            .find(|x| same_address(&x.server.address, &host, port))
+            .cloned()
+            .ok_or(StatusError::NotMonitored)
+    }
+
+    fn history(&self) -> Result<&History, StatusError> {
+        self.history.as_ref().ok_or(StatusError::HistoryError)
+    }
+
+    /// Adds `server`, refusing it if it is monitored already.
+    pub async fn add(&self, server: MonitoredServer) -> Result<Entry, StatusError> {
+        let _changes = self.changes.lock().await;
+        let (host, port) = parse_address(&server.address, None)?;
+        let host = cache::normalize_host(host);
+        if self.entries.lock().unwrap().iter().any(|x| same_address(&x.server.address, &host, port)) {
+            return Err(StatusError::AlreadyMonitored);
+        }
+
+        self.store(&server).await?;
+        let entry = Entry { server, source: Source::Admin };
+        self.entries.lock().unwrap().push(entry.clone());
+        Ok(entry)
+    }
+
+    /// Replaces the server added at runtime that `address`
+    /// refers to with `server`, keeping its address.
+    pub async fn update(&self, address: &str, mut server: MonitoredServer) -> Result<Entry, StatusError> {
+        let _changes = self.changes.lock().await;
+        let existing = self.find(address, None)?;
+        if existing.source == Source::Config {
+            return Err(StatusError::Configured);
+        }
+
+        server.address = existing.server.address;
+        self.store(&server).await?;
+        let entry = Entry { server, source: Source::Admin };
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(x) = entries.iter_mut().find(|x| x.server.address == entry.server.address) {
+            *x = entry.clone();
+        }
+        Ok(entry)
+    }
+
+    /// Removes the server added at runtime that `address`
+    /// refers to.
+    pub async fn remove(&self, address: &str) -> Result<Entry, StatusError> {
+        let _changes = self.changes.lock().await;
+        let existing = self.find(address, None)?;
+        if existing.source == Source::Config {
+            return Err(StatusError::Configured);
+        }
+
+        let address = existing.server.address.as_str();
+        self.history()?.remove_server(address).await.map_err(|e| store_error(address, &e))?;
+        self.entries.lock().unwrap().retain(|x| x.server.address != address);
+        Ok(existing)
+    }
+
+    /// Adds each of `servers`, replacing the settings of the
+    /// ones added at runtime before. Responds the imported
+    /// servers and the ones that couldn't be imported.
+    pub async fn import(&self, servers: Vec<MonitoredServer>) -> (Vec<Entry>, Vec<SkippedServer>) {
+        let (mut imported, mut skipped) = (Vec::new(), Vec::new());
+        for server in servers {
+            let address = server.address.clone();
+            let result = match self.add(server.clone()).await {
+                Err(StatusError::AlreadyMonitored) => self.update(&address, server).await,
+                result => result,
+            };
+            match result {
+                Ok(entry) => imported.push(entry),
+                Err(err) => skipped.push(SkippedServer { address, err }),
+            }
+        }
+        (imported, skipped)
+    }
+
+    async fn store(&self, server: &MonitoredServer) -> Result<(), StatusError> {
+        let config = serde_json::to_string(server).map_err(|_| StatusError::InvalidInput)?;
+        self.history()?.store_server(&server.address, &config).await.map_err(|e| store_error(&server.address, &e))
+    }
+}
@@ -0,0 +1,72 @@
+//! Slack incoming webhook messages about monitored servers
+//! going online or offline, formatted with Block Kit.
+
+use rocket::serde::json::{serde_json, Value};
+use crate::notify::{self, StatusChange};
+
+/// Escapes the characters Slack's mrkdwn treats specially.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Builds the Block Kit message about `change`, starting with
+/// `text`, which is also the fallback for notifications.
+fn message(change: &StatusChange, text: &str) -> Value {
+    let title = format!("{} is {}", change.server, if change.online() { "online" } else { "offline" });
+    let duration = notify::format_duration(change.previous_duration);
+
+    let mut fields = Vec::new();
+    if let Some(ping) = &change.ping {
+        let players = &ping.status.players;
+        fields.push(format!("*Players*\n{}/{}", players.online, players.max));
+        fields.push(format!("*Version*\n{}", escape(&ping.status.version.name)));
+        fields.push(format!("*Downtime*\n{}", duration));
+    } else {
+        fields.push(format!("*Was online for*\n{}", duration));
+    }
+
+    let mut blocks = vec![
+        serde_json::json!({
+            "type": "header",
+            "text": { "type": "plain_text", "text": title },
+        }),
+        serde_json::json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": escape(text) },
+        }),
+        serde_json::json!({
+            "type": "section",
+            "fields": fields.into_iter().map(|x| serde_json::json!({ "type": "mrkdwn", "text": x })).collect::<Vec<_>>(),
+        }),
+    ];
+    if let Some(motd) = change.ping.as_ref().map(|x| &x.status.motd).filter(|x| !x.is_empty()) {
+        blocks.push(serde_json::json!({
+            "type": "context",
+            "elements": [{ "type": "mrkdwn", "text": escape(motd) }],
+        }));
+    }
+
+    serde_json::json!({
+        "text": text,
+        "blocks": blocks,
+    })
+}
+
+/// Posts the message about `change` with `text` to the
+/// incoming webhook at `url`.
+pub async fn send(client: &reqwest::Client, url: &str, change: &StatusChange, text: &str) -> Result<(), reqwest::Error> {
+    client.post(url)
+        .json(&message(change, text))
+        .send().await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Posts `text` alone to the incoming webhook at `url`.
+pub async fn send_text(client: &reqwest::Client, url: &str, text: &str) -> Result<(), reqwest::Error> {
+    client.post(url)
+        .json(&serde_json::json!({ "text": escape(text) }))
+        .send().await?
+        .error_for_status()?;
+    Ok(())
+}
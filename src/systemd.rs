@@ -0,0 +1,60 @@
+//! Telling systemd when the checker is ready and that it is
+//! still alive, for `Type=notify` units with a watchdog.
+
+use std::sync::Arc;
+use std::time::Duration;
+use rocket::{Orbit, Rocket};
+use rocket::fairing::{Fairing, Info, Kind};
+use sd_notify::NotifyState;
+use crate::history::History;
+use crate::monitor::Heartbeat;
+
+/// Notifies systemd after liftoff and on shutdown, and pings
+/// its watchdog if enabled. The pings stop if the runtime or
+/// the scheduler is stuck, so systemd restarts the service.
+pub struct Systemd;
+
+#[rocket::async_trait]
+impl Fairing for Systemd {
+    fn info(&self) -> Info {
+        Info {
+            name: "systemd",
+            kind: Kind::Liftoff | Kind::Shutdown,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        if let Err(e) = sd_notify::notify(false, &[NotifyState::Ready]) {
+            warn!("can't notify systemd: {}", e);
+        }
+
+        let mut usec = 0;
+        if sd_notify::watchdog_enabled(false, &mut usec) {
+            // Scheduling without history leaves the heartbeat
+            // alone, which then mustn't hold back the pings
+            let scheduling = matches!(rocket.state::<Option<History>>(), Some(Some(_)));
+            let heartbeat = rocket.state::<Arc<Heartbeat>>().filter(|_| scheduling).cloned();
+            tokio::spawn(watchdog(Duration::from_micros(usec), heartbeat));
+        }
+    }
+
+    async fn on_shutdown(&self, _: &Rocket<Orbit>) {
+        let _ = sd_notify::notify(false, &[NotifyState::Stopping]);
+    }
+}
+
+/// Pings the watchdog every half of its `timeout` while the
+/// scheduler of the `heartbeat` keeps looking for servers.
+async fn watchdog(timeout: Duration, heartbeat: Option<Arc<Heartbeat>>) {
+    let mut interval = tokio::time::interval(timeout / 2);
+    loop {
+        interval.tick().await;
+        if heartbeat.as_ref().is_some_and(|x| !x.alive(timeout)) {
+            warn!("not pinging the watchdog of systemd as the scheduler is stuck");
+            continue;
+        }
+        if let Err(e) = sd_notify::notify(false, &[NotifyState::Watchdog]) {
+            warn!("can't ping the watchdog of systemd: {}", e);
+        }
+    }
+}
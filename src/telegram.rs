@@ -0,0 +1,41 @@
+//! Telegram bot messages about monitored servers going
+//! online or offline.
+
+use futures::future;
+use serde::Serialize;
+use thiserror::Error;
+use crate::config::TelegramConfig;
+
+#[derive(Debug, Serialize)]
+struct SendMessage<'a> {
+    chat_id: i64,
+    text: &'a str,
+}
+
+/// How many chats of the bot a message couldn't be sent to.
+#[derive(Debug, Error)]
+#[error("{failed} of {total} chats couldn't be sent the message")]
+pub struct SendError {
+    pub failed: usize,
+    pub total: usize,
+}
+
+/// Sends `text` to all chats of the bot at once, so a chat
+/// that blocked the bot doesn't keep it from the others.
+/// Each failure is logged.
+pub async fn send(client: &reqwest::Client, config: &TelegramConfig, text: &str) -> Result<(), SendError> {
+    let url = format!("https://api.telegram.org/bot{}/sendMessage", config.token);
+    let sends = config.chat_ids.iter().map(|&chat_id| {
+        let request = client.post(&url).json(&SendMessage { chat_id, text });
+        async move {
+            let result = request.send().await.and_then(|x| x.error_for_status());
+            // Errors would contain the URL with the bot token
+            result.map_err(|e| warn!("can't send a Telegram message to chat {}: {}", chat_id, e.without_url()))
+        }
+    });
+    let failed = future::join_all(sends).await.iter().filter(|x| x.is_err()).count();
+    match failed {
+        0 => Ok(()),
+        failed => Err(SendError { failed, total: config.chat_ids.len() }),
+    }
+}
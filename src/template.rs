@@ -0,0 +1,24 @@
+//! Notification texts with `{{name}}` placeholders for the
+//! variables of a status change.
+
+/// Replaces every `{{name}}` in `template` with the value of
+/// the variable `name`, ignoring whitespace around the name.
+/// Unknown placeholders are left as they are.
+pub fn render(template: &str, variables: &[(&str, String)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}").map(|x| start + 2 + x) else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let name = rest[start + 2..end].trim();
+        match variables.iter().find(|(x, _)| *x == name) {
+            Some((_, value)) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
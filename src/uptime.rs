@@ -0,0 +1,128 @@
+//! The availability of monitored servers, computed from
+//! their stored checks.
+
+use serde::Serialize;
+use crate::history::Check;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// The longest time checks are looked back at.
+pub const MAX_WINDOW: u64 = 30 * DAY;
+
+/// How much of the time a server was online.
+#[derive(Debug, Serialize)]
+pub struct Uptime {
+    /// The percentage of checks in the last 24 hours the
+    /// server was online at, absent without checks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_24h: Option<f64>,
+
+    /// The same for the last 7 days.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_7d: Option<f64>,
+
+    /// The same for the last 30 days.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_30d: Option<f64>,
+
+    /// The longest time in the last 30 days the server was
+    /// offline for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longest_outage: Option<Outage>,
+
+    /// How long the server has been online or offline for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_streak: Option<Streak>,
+}
+
+/// A time the server was offline.
+#[derive(Debug, Serialize)]
+pub struct Outage {
+    /// The first check the server was offline at, in seconds
+    /// since the Unix epoch.
+    pub start: u64,
+
+    /// The first check the server was online at again, or the
+    /// current time if it still is offline.
+    pub end: u64,
+
+    /// How long the outage lasted in seconds.
+    pub duration: u64,
+
+    /// Whether the server still is offline.
+    pub ongoing: bool,
+}
+
+/// The time since the server was last online or offline.
+#[derive(Debug, Serialize)]
+pub struct Streak {
+    pub online: bool,
+
+    /// The first check of the streak, in seconds since the
+    /// Unix epoch.
+    pub since: u64,
+
+    /// How long the streak lasts in seconds.
+    pub duration: u64,
+}
+
+/// Consecutive checks with the same result.
+struct Run {
+    online: bool,
+    start: u64,
+
+    /// The first check of the next run, or the current time
+    /// for the last one.
+    end: u64,
+}
+
+/// Computes the uptime from `checks` of the last 30 days,
+/// oldest first, at `now` in seconds since the Unix epoch.
+/// Checks under maintenance are left out.
+pub fn compute(checks: &[Check], now: u64) -> Uptime {
+    let checks: Vec<Check> = checks.iter().filter(|x| !x.maintenance).copied().collect();
+    let checks = checks.as_slice();
+    let runs = runs(checks, now);
+    let last = runs.len().checked_sub(1);
+
+    let longest_outage = runs.iter().enumerate()
+        .filter(|(_, x)| !x.online)
+        .max_by_key(|(_, x)| x.end.saturating_sub(x.start))
+        .map(|(i, x)| Outage { start: x.start, end: x.end, duration: x.end.saturating_sub(x.start), ongoing: Some(i) == last });
+    let current_streak = runs.last()
+        .map(|x| Streak { online: x.online, since: x.start, duration: x.end.saturating_sub(x.start) });
+
+    Uptime {
+        last_24h: availability(checks, now.saturating_sub(DAY)),
+        last_7d: availability(checks, now.saturating_sub(7 * DAY)),
+        last_30d: availability(checks, now.saturating_sub(MAX_WINDOW)),
+        longest_outage,
+        current_streak,
+    }
+}
+
+/// The percentage of checks since `since` the server was
+/// online at, rounded to two decimals.
+fn availability(checks: &[Check], since: u64) -> Option<f64> {
+    let checks: Vec<&Check> = checks.iter().filter(|x| x.checked_at >= since).collect();
+    if checks.is_empty() {
+        return None;
+    }
+    let online = checks.iter().filter(|x| x.online).count();
+    Some((online as f64 / checks.len() as f64 * 10000.0).round() / 100.0)
+}
+
+fn runs(checks: &[Check], now: u64) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for check in checks {
+        match runs.last_mut() {
+            Some(run) if run.online == check.online => {}
+            Some(run) => {
+                run.end = check.checked_at;
+                runs.push(Run { online: check.online, start: check.checked_at, end: now });
+            }
+            None => runs.push(Run { online: check.online, start: check.checked_at, end: now }),
+        }
+    }
+    runs
+}
@@ -0,0 +1,75 @@
+//! Probing which protocol versions a server accepts, for
+//! checking the range of ViaVersion or ProtocolSupport.
+
+use futures::future::join_all;
+use serde::Serialize;
+use mcstatus_checker::java::{self, PingOptions, ServerVersion};
+use crate::error::StatusError;
+
+/// The last protocol version of every major release since
+/// the Netty rewrite, probed if no versions are given.
+pub const DEFAULT_PROTOCOLS: [(i32, &str); 13] = [
+    (5, "1.7.10"),
+    (47, "1.8.9"),
+    (110, "1.9.4"),
+    (210, "1.10.2"),
+    (316, "1.11.2"),
+    (340, "1.12.2"),
+    (404, "1.13.2"),
+    (498, "1.14.4"),
+    (578, "1.15.2"),
+    (754, "1.16.5"),
+    (756, "1.17.1"),
+    (758, "1.18.2"),
+    (760, "1.19.2"),
+];
+
+/// The outcome of the handshake with one protocol version.
+#[derive(Debug, Serialize)]
+pub struct VersionProbe {
+    /// The protocol version sent in the handshake.
+    pub protocol: i32,
+
+    /// The release of the protocol version, if it's one of
+    /// the known ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'static str>,
+
+    /// Whether the server reported the same protocol version
+    /// back, meaning a client of it can join.
+    pub accepted: bool,
+
+    /// The version the server reported for this handshake.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reported: Option<ServerVersion>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<StatusError>,
+}
+
+/// Performs a handshake for each of `protocols` concurrently,
+/// otherwise with the given `options`.
+pub async fn probe(host: &str, port: u16, options: &PingOptions, protocols: &[i32]) -> Vec<VersionProbe> {
+    join_all(protocols.iter().map(|&protocol| async move {
+        let options = PingOptions { protocol, ..options.clone() };
+        let result = java::ping(host, port, &options).await;
+        let name = DEFAULT_PROTOCOLS.iter().find(|x| x.0 == protocol).map(|x| x.1);
+
+        match result {
+            Ok(ping) => VersionProbe {
+                protocol,
+                name,
+                accepted: ping.status.version.protocol == protocol,
+                reported: Some(ping.status.version),
+                err: None,
+            },
+            Err(e) => VersionProbe {
+                protocol,
+                name,
+                accepted: false,
+                reported: None,
+                err: Some(e.into()),
+            },
+        }
+    })).await
+}